@@ -0,0 +1,162 @@
+//! Throughput of decoding danmu server messages, against the recursive
+//! decoder it replaced.
+
+use std::io::Write;
+
+use blive_greeting::{Operation, PacketHeader, ProtoVer, SubDecoder};
+use bytes::{BufMut, Bytes, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// A `DANMU_MSG` as sent in a busy room.
+const DANMU: &str = r#"{"cmd":"DANMU_MSG","info":[[0,1,25,16777215,1700000000000,1700000000,0,"5d2c1f3a",0,0,0,"",0,"{}","{}",{"mode":0,"show_player_type":0,"extra":"{\"send_from_me\":false,\"mode\":0,\"color\":16777215,\"dm_type\":0,\"font_size\":25,\"player_mode\":1,\"show_player_type\":0,\"content\":\"来了来了\",\"user_hash\":\"1546395450\",\"emoticon_unique\":\"\",\"bulge_display\":0,\"recommend_score\":1,\"main_state_dm_color\":\"\",\"objective_state_dm_color\":\"\",\"direction\":0,\"pk_direction\":0,\"quartet_direction\":0,\"anniversary_crowd\":0,\"yeah_space_type\":\"\",\"yeah_space_url\":\"\",\"jump_to_url\":\"\",\"space_type\":\"\",\"space_url\":\"\",\"animation\":{},\"emots\":null,\"is_audited\":false,\"id_str\":\"0123456789abcdef\",\"icon\":null,\"show_reply\":true,\"reply_mid\":0,\"reply_uname\":\"\",\"reply_uname_color\":\"\",\"reply_is_mystery\":false,\"hit_combo\":0}"}],"来了来了",[12345678,"观众",0,0,0,10000,1,""],[21,"粉丝团","主播",1234,1725515,"",0,1725515,1725515,5414290,0,1,1234],[12,0,6406234,">50000",0],["",""],0,0,null,{"ts":1700000000,"ct":"A1B2C3D4"},0,0,null,null,0,105,[0]]}"#;
+
+/// Plain packets of `count` danmu, as inside a compressed packet.
+fn packets(count: usize) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    for _ in 0..count {
+        PacketHeader::new(Operation::Message, ProtoVer::Json, DANMU.len()).encode(&mut buf);
+        buf.put_slice(DANMU.as_bytes());
+    }
+    buf.to_vec()
+}
+
+/// A websocket message of `count` danmu compressed with `proto_ver`.
+fn message(count: usize, proto_ver: ProtoVer) -> Bytes {
+    let packets = packets(count);
+    let body = match proto_ver {
+        ProtoVer::Zlib => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&packets).unwrap();
+            encoder.finish().unwrap()
+        }
+        ProtoVer::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            encoder.write_all(&packets).unwrap();
+            encoder.into_inner()
+        }
+        _ => return Bytes::from(packets),
+    };
+
+    let mut buf = BytesMut::new();
+    PacketHeader::new(Operation::Message, proto_ver, body.len()).encode(&mut buf);
+    buf.put_slice(&body);
+    buf.freeze()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    for proto_ver in [ProtoVer::Json, ProtoVer::Zlib, ProtoVer::Brotli] {
+        let message = message(200, proto_ver);
+
+        let mut group = c.benchmark_group(format!("decode/{proto_ver:?}"));
+        group.throughput(Throughput::Elements(200));
+
+        group.bench_function("recursive", |b| {
+            b.iter(|| {
+                for reply in baseline::decode(message.clone()).unwrap() {
+                    black_box(reply);
+                }
+            })
+        });
+
+        group.bench_function("iterative", |b| {
+            let mut decoder = SubDecoder::new();
+            b.iter(|| {
+                for reply in decoder.decode(message.clone()) {
+                    black_box(reply.unwrap());
+                }
+            })
+        });
+
+        group.finish();
+    }
+}
+
+/// The recursive decoder `SubDecoder` replaced, as it was.
+mod baseline {
+    use std::io::Read;
+
+    use blive_greeting::{Error, Operation, PacketHeader, ProtoVer, SubReply};
+    use brotli::Decompressor;
+    use bytes::{Buf, Bytes};
+    use flate2::read::ZlibDecoder;
+
+    const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Deepest nesting of compressed packets accepted. Bilibili only compresses
+    /// plain packets, so anything deeper is malformed.
+    const MAX_DEPTH: usize = 2;
+
+    /// Takes `Bytes` rather than the websocket message's `Vec`, which converts
+    /// without copying.
+    pub fn decode(data: Bytes) -> Result<Vec<SubReply>, Error> {
+        let mut replies = Vec::new();
+        decode_bytes(data, 0, &mut replies)?;
+        Ok(replies)
+    }
+
+    fn decode_bytes(
+        mut data: Bytes,
+        depth: usize,
+        replies: &mut Vec<SubReply>,
+    ) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::DecodeSub("depth"));
+        }
+
+        while data.has_remaining() {
+            let header = PacketHeader::decode(&mut data)?;
+            if data.remaining() < header.body_len() {
+                return Err(Error::DecodeSub("body"));
+            }
+
+            let body = data.split_to(header.body_len());
+            decode_packet(header, body, depth, replies)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the body of a packet, inflating the packets compressed in it.
+    fn decode_packet(
+        header: PacketHeader,
+        body: Bytes,
+        depth: usize,
+        replies: &mut Vec<SubReply>,
+    ) -> Result<(), Error> {
+        match (header.proto_ver, header.operation) {
+            (ProtoVer::Zlib, Operation::Message) => {
+                let output = inflate(ZlibDecoder::new(body.reader()))?;
+                decode_bytes(output, depth + 1, replies)?;
+            }
+            (ProtoVer::Brotli, Operation::Message) => {
+                let output = inflate(Decompressor::new(body.reader(), 4096))?;
+                decode_bytes(output, depth + 1, replies)?;
+            }
+            (_, Operation::Message) => replies.push(SubReply::Message(body)),
+            (_, Operation::HeartbeatReply) => replies.push(SubReply::Heartbeat(body)),
+            (_, Operation::AuthReply) => replies.push(SubReply::Auth(body)),
+            _ => replies.push(SubReply::Unknown(header, body)),
+        }
+
+        Ok(())
+    }
+
+    /// Reads a decompressor to the end, up to `MAX_PACKET_SIZE`.
+    fn inflate(decoder: impl Read) -> Result<Bytes, Error> {
+        let mut output = Vec::new();
+        decoder
+            .take(MAX_PACKET_SIZE as u64 + 1)
+            .read_to_end(&mut output)
+            .map_err(|_| Error::DecodeSub("compressed body"))?;
+
+        if output.len() > MAX_PACKET_SIZE {
+            return Err(Error::DecodeSub("inflated size"));
+        }
+
+        Ok(Bytes::from(output))
+    }
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
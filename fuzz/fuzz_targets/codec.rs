@@ -0,0 +1,22 @@
+#![no_main]
+
+use blive_greeting::SubCodec;
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::Decoder;
+
+// Feeds the input in small chunks, as a TCP stream would deliver it.
+fuzz_target!(|data: &[u8]| {
+    let mut codec = SubCodec::new();
+    let mut buf = BytesMut::new();
+    for chunk in data.chunks(7) {
+        buf.extend_from_slice(chunk);
+        loop {
+            match codec.decode(&mut buf) {
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                Err(_) => return,
+            }
+        }
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use blive_greeting::sub::decode;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode(data.to_vec());
+});
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use biliup::credential::{login_by_cookies, LoginInfo};
+use blive_greeting::client::{BiliClient, ClientConfig};
+use blive_greeting::credential::{expires, refresh, save, BiliCredentials};
+use blive_greeting::{Buvid, Result};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::config::{AccountConfig, CredentialConfig};
+
+pub struct Account {
+    pub name: String,
+    /// Cookie file of the account.
+    path: String,
+    /// Client of the account, whose cookies are updated when refreshed.
+    pub client: BiliClient,
+}
+
+impl Account {
+    /// Checks the login, refreshing the cookies if they are invalid or about
+    /// to expire. Returns `false` if the account needs to log in again.
+    pub async fn check(&self, config: &CredentialConfig) -> Result<bool> {
+        let nav = self.client.user_info().await?;
+
+        let login: Value = serde_json::from_str(&tokio::fs::read_to_string(&self.path).await?)?;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let expiring = expires(&login).is_some_and(|t| t - now < config.refresh_before as i64);
+
+        if nav.is_login && !expiring {
+            debug!("account {} logged in as {}", self.name, nav.uname);
+            return Ok(true);
+        }
+
+        match refresh(&login).await {
+            Ok(login) => {
+                save(&self.path, &login).await?;
+                let login_info: LoginInfo = serde_json::from_value(login)?;
+                self.client.credentials().update(&login_info);
+                info!("account {} refreshed", self.name);
+                Ok(true)
+            }
+            Err(e) if nav.is_login => {
+                warn!("account {} failed to refresh: {e}", self.name);
+                Ok(true)
+            }
+            Err(e) => {
+                warn!(
+                    "account {} logged out and failed to refresh: {e}",
+                    self.name
+                );
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Every logged in account.
+pub struct Accounts {
+    accounts: Vec<(AccountConfig, Arc<Account>)>,
+}
+
+impl Accounts {
+    pub async fn login(configs: &[AccountConfig], http: &ClientConfig, fetch_buvid: bool) -> Self {
+        let mut accounts = Vec::new();
+        for config in configs {
+            let bili = login_by_cookies(&config.cookies)
+                .await
+                .unwrap_or_else(|e| panic!("failed to login {}: {e}", config.name));
+            info!("logged in as {}", config.name);
+
+            let credentials = BiliCredentials::from_login_info(&bili.login_info);
+            let client = BiliClient::new(credentials, http.clone())
+                .unwrap_or_else(|e| panic!("failed to build client of {}: {e}", config.name));
+            let buvid = match &config.buvid {
+                Some(path) => path.clone(),
+                None => format!("buvid_{}.json", config.name),
+            };
+            init_buvid(&client, &config.name, &buvid, fetch_buvid).await;
+
+            let account = Account {
+                name: config.name.clone(),
+                path: config.cookies.clone(),
+                client,
+            };
+            accounts.push((config.clone(), Arc::new(account)));
+        }
+
+        Self { accounts }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Account>> {
+        self.accounts.iter().map(|(_, account)| account)
+    }
+
+    /// Accounts for a room in order of preference: those preferring the
+    /// room first, then the general ones.
+    pub fn for_room(&self, room_id: u32) -> Vec<Arc<Account>> {
+        let preferred = self
+            .accounts
+            .iter()
+            .filter(|(config, _)| config.rooms.contains(&room_id));
+        let general = self
+            .accounts
+            .iter()
+            .filter(|(config, _)| config.rooms.is_empty());
+
+        let accounts: Vec<_> = preferred
+            .chain(general)
+            .map(|(_, account)| account.clone())
+            .collect();
+
+        if accounts.is_empty() {
+            // Every account is dedicated to other rooms; better than nothing.
+            self.iter().cloned().collect()
+        } else {
+            accounts
+        }
+    }
+}
+
+/// A client without account, for connecting to rooms as guest.
+pub async fn guest(http: &ClientConfig, fetch_buvid: bool) -> BiliClient {
+    let client = BiliClient::new(BiliCredentials::new(), http.clone())
+        .unwrap_or_else(|e| panic!("failed to build guest client: {e}"));
+    init_buvid(&client, "guest", "buvid_guest.json", fetch_buvid).await;
+    client
+}
+
+/// Sets the buvid of an account: the persisted one, a fetched and activated
+/// one, or a generated one if Bilibili is unreachable.
+async fn init_buvid(client: &BiliClient, name: &str, path: &str, fetch: bool) {
+    let saved = tokio::fs::read_to_string(path)
+        .await
+        .ok()
+        .and_then(|s| serde_json::from_str::<Buvid>(&s).ok());
+    if let Some(buvid) = saved {
+        client.credentials().set_buvid(&buvid);
+        return;
+    }
+
+    if fetch {
+        match client.spi().await {
+            Ok(buvid) => {
+                client.credentials().set_buvid(&buvid);
+                if let Err(e) = client.activate_buvid().await {
+                    warn!("failed to activate buvid of {name}: {e}");
+                }
+
+                let saved = match serde_json::to_value(&buvid) {
+                    Ok(value) => save(path, &value).await,
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = saved {
+                    warn!("failed to save buvid of {name}: {e}");
+                }
+
+                info!("fetched buvid of {name}");
+                return;
+            }
+            Err(e) => warn!("failed to fetch buvid of {name}, generating: {e}"),
+        }
+    }
+
+    client.credentials().set_buvid(&Buvid::generate());
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use blive_greeting::template::render;
+use blive_greeting::{DanmuMessage, LiveMessage};
+use tokio::sync::Mutex;
+
+use crate::config::{AutoReplyConfig, ReplyRule};
+use crate::sender::DanmuSender;
+
+pub struct AutoReplier {
+    config: AutoReplyConfig,
+    last_replied: Mutex<Vec<Option<Instant>>>,
+}
+
+impl AutoReplier {
+    pub fn new(config: AutoReplyConfig) -> Self {
+        Self {
+            last_replied: Mutex::new(vec![None; config.rules.len()]),
+            config,
+        }
+    }
+
+    pub async fn handle_message(&self, message: &LiveMessage, sender: &DanmuSender<'_>) {
+        let danmu = match message {
+            LiveMessage::Danmu(danmu) => danmu,
+            _ => return,
+        };
+
+        if sender.is_own(danmu.uid) {
+            return;
+        }
+
+        let rule = {
+            let mut last_replied = self.last_replied.lock().await;
+            let found = self.config.rules.iter().enumerate().find(|(i, rule)| {
+                let cooling = last_replied[*i]
+                    .is_some_and(|t| t.elapsed() < Duration::from_secs(rule.cooldown));
+                !cooling && matches(rule, danmu)
+            });
+
+            match found {
+                Some((i, rule)) => {
+                    last_replied[i] = Some(Instant::now());
+                    rule
+                }
+                None => return,
+            }
+        };
+
+        let uid = danmu.uid.to_string();
+        let reply = render(
+            &rule.reply,
+            &[
+                ("uname", danmu.uname.as_str()),
+                ("uid", &uid),
+                ("content", &danmu.content),
+            ],
+        );
+        sender.reply(&reply, danmu.uid).await;
+    }
+}
+
+fn matches(rule: &ReplyRule, danmu: &DanmuMessage) -> bool {
+    if !rule.uids.is_empty() && !rule.uids.contains(&danmu.uid) {
+        return false;
+    }
+
+    if let Some(level) = rule.medal_level {
+        if danmu.medal.as_ref().map_or(0, |m| m.level) < level {
+            return false;
+        }
+    }
+
+    rule.pattern.is_match(&danmu.content)
+}
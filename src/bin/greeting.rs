@@ -1,7 +1,8 @@
 use biliup::credential::login_by_cookies;
+use blive_greeting::client::{BiliClient, ClientConfig};
+use blive_greeting::credential::BiliCredentials;
 use blive_greeting::danmu::send_greeting;
-use blive_greeting::gen_buvid3;
-use blive_greeting::info::bili_cookies;
+use blive_greeting::Buvid;
 use tracing::{error, info};
 
 #[tokio::main]
@@ -12,9 +13,23 @@ async fn main() {
 
     tracing_subscriber::fmt::init();
 
-    let bili = login_by_cookies("cookies.json")
-        .await
-        .expect("failed to login");
+    // Cookie files of the accounts to greet as, tried in order.
+    let mut files: Vec<String> = std::env::args().skip(1).collect();
+    if files.is_empty() {
+        files.push("cookies.json".to_string());
+    }
+
+    let mut accounts = Vec::new();
+    for file in &files {
+        let bili = login_by_cookies(file)
+            .await
+            .unwrap_or_else(|e| panic!("failed to login {file}: {e}"));
+        let credentials = BiliCredentials::from_login_info(&bili.login_info);
+        credentials.set_buvid(&Buvid::generate());
+        let client =
+            BiliClient::new(credentials, ClientConfig::default()).expect("failed to build client");
+        accounts.push(client);
+    }
 
     let rooms = tokio::fs::read_to_string("rooms.txt")
         .await
@@ -22,17 +37,19 @@ async fn main() {
 
     for room_id in rooms.lines() {
         let room_id = room_id.parse().expect("invalid room id");
-        run(&bili, room_id).await;
+        run(&accounts, room_id).await;
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     }
 }
 
-async fn run(bili: &biliup::bilibili::BiliBili, room_id: u32) {
-    let buvid = gen_buvid3();
-    let cookies = bili_cookies(&bili.login_info, &buvid);
-
-    match send_greeting(&cookies, room_id).await {
-        Ok(_) => info!("[{}] greeting sent", room_id),
-        Err(e) => error!("[{}] send greeting error: {e}", room_id),
+async fn run(accounts: &[BiliClient], room_id: u32) {
+    for client in accounts {
+        match send_greeting(client, room_id).await {
+            Ok(_) => {
+                info!("[{}] greeting sent", room_id);
+                return;
+            }
+            Err(e) => error!("[{}] send greeting error: {e}", room_id),
+        }
     }
 }
@@ -0,0 +1,110 @@
+use blive_greeting::ntqq::{MessageEvent, OneBot, OneBotEvent};
+use blive_greeting::Result;
+use futures_util::StreamExt;
+use tracing::{debug, error, info};
+
+use crate::config::OneBotConfig;
+use crate::rooms::RoomManager;
+
+const USAGE: &str = "usage: /watch <room> | /unwatch <room> | /greet <room> | /status";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Watch(u32),
+    Unwatch(u32),
+    Greet(u32),
+    Status,
+}
+
+impl Command {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut args = text.split_whitespace();
+        let command = args.next()?;
+        let room_id = args.next().map(|s| s.parse::<u32>());
+
+        let command = match (command, room_id) {
+            ("/watch", Some(Ok(room_id))) => Command::Watch(room_id),
+            ("/unwatch", Some(Ok(room_id))) => Command::Unwatch(room_id),
+            ("/greet", Some(Ok(room_id))) => Command::Greet(room_id),
+            ("/status", None) => Command::Status,
+            _ => return None,
+        };
+
+        match args.next() {
+            Some(_) => None,
+            None => Some(command),
+        }
+    }
+}
+
+pub async fn run(config: &OneBotConfig, rooms: &RoomManager) {
+    let onebot = OneBot::new(&config.endpoint, &config.token);
+    loop {
+        match serve(&onebot, config, rooms).await {
+            Ok(_) => info!("onebot event stream closed"),
+            Err(e) => error!("onebot event stream error: {e}"),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn serve(onebot: &OneBot, config: &OneBotConfig, rooms: &RoomManager) -> Result<()> {
+    let mut events = std::pin::pin!(onebot.events(&config.event_url).await?);
+    info!("onebot event stream connected");
+
+    while let Some(event) = events.next().await {
+        let message = match event {
+            Ok(OneBotEvent::Message(message)) => message,
+            Ok(OneBotEvent::Other) => continue,
+            Err(e) => {
+                debug!("skip onebot event: {e}");
+                continue;
+            }
+        };
+
+        if !message.raw_message.starts_with('/') {
+            continue;
+        }
+
+        if !config.admins.contains(&message.user_id) {
+            debug!("ignore command from {}", message.user_id);
+            continue;
+        }
+
+        let reply = execute(&message, rooms).await;
+        if let Err(e) = onebot.send_message(&message.peer(), &reply).await {
+            error!("failed to reply to {}: {e}", message.user_id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn execute(message: &MessageEvent, rooms: &RoomManager) -> String {
+    let command = match Command::parse(&message.raw_message) {
+        Some(command) => command,
+        None => return USAGE.to_string(),
+    };
+
+    info!("{} issued {command:?}", message.user_id);
+
+    match command {
+        Command::Watch(room_id) => match rooms.watch(room_id).await {
+            true => format!("watching {room_id}"),
+            false => format!("already watching {room_id}"),
+        },
+        Command::Unwatch(room_id) => match rooms.unwatch(room_id).await {
+            true => format!("unwatched {room_id}"),
+            false => format!("not watching {room_id}"),
+        },
+        Command::Greet(room_id) => match rooms.greet(room_id).await {
+            Ok(_) => format!("greeting sent to {room_id}"),
+            Err(e) => format!("failed to greet {room_id}: {e}"),
+        },
+        Command::Status => {
+            let rooms = rooms.rooms().await;
+            let list: Vec<String> = rooms.iter().map(|r| r.to_string()).collect();
+            format!("watching {} rooms: {}", rooms.len(), list.join(", "))
+        }
+    }
+}
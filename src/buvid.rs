@@ -1,5 +1,7 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+
 fn gen_hex(len: u32) -> String {
     let mut hex = String::new();
     for _ in 0..len {
@@ -30,3 +32,36 @@ pub fn gen_buvid3() -> String {
         gen_ts()
     )
 }
+
+/// Device identifiers sent as cookies, from the SPI endpoint or generated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Buvid {
+    pub buvid3: String,
+    /// Empty when generated.
+    #[serde(default)]
+    pub buvid4: String,
+    pub b_nut: String,
+    #[serde(rename = "_uuid")]
+    pub uuid: String,
+}
+
+impl Buvid {
+    /// Fabricates identifiers, for when the SPI endpoint is unreachable.
+    pub fn generate() -> Self {
+        Self {
+            buvid3: gen_buvid3(),
+            buvid4: String::new(),
+            b_nut: b_nut(),
+            uuid: gen_buvid3(),
+        }
+    }
+}
+
+/// Unix time when the identifiers are issued.
+pub(crate) fn b_nut() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string()
+}
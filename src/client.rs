@@ -0,0 +1,341 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::buvid::b_nut;
+use crate::credential::BiliCredentials;
+use crate::info::{BiliResponse, DanmuInfo, Nav, PlayInfo, USER_AGENT};
+use crate::wbi::{sign, WbiSigner};
+use crate::Error::{self, MissingData};
+use crate::{gen_buvid3, Buvid};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientConfig {
+    #[serde(default = "default_live_url")]
+    pub live_url: String,
+    #[serde(default = "default_live_api_url")]
+    pub live_api_url: String,
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    /// Seconds before a request times out.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Seconds before connecting times out.
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            live_url: default_live_url(),
+            live_api_url: default_live_api_url(),
+            api_url: default_api_url(),
+            timeout: default_timeout(),
+            connect_timeout: default_connect_timeout(),
+        }
+    }
+}
+
+/// HTTP client of an account, shared by every room so that connections and
+/// cookies set by responses are reused.
+#[derive(Debug, Clone)]
+pub struct BiliClient {
+    client: reqwest::Client,
+    credentials: BiliCredentials,
+    config: Arc<ClientConfig>,
+    wbi: Arc<WbiSigner>,
+}
+
+impl BiliClient {
+    pub fn new(credentials: BiliCredentials, config: ClientConfig) -> Result<Self, Error> {
+        let urls = [&config.live_url, &config.live_api_url, &config.api_url].map(String::as_str);
+        let client = reqwest::Client::builder()
+            .cookie_provider(credentials.cookie_store(&urls))
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(config.timeout))
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .build()?;
+
+        Ok(Self {
+            client,
+            credentials,
+            config: Arc::new(config),
+            wbi: Arc::default(),
+        })
+    }
+
+    pub fn credentials(&self) -> &BiliCredentials {
+        &self.credentials
+    }
+
+    pub async fn room_info(&self, room_id: u32) -> Result<PlayInfo, Error> {
+        // Visit the room first for the cookies it sets.
+        self.client
+            .get(format!("{}/{room_id}", self.config.live_url))
+            .send()
+            .await?;
+
+        let play_info = self
+            .client
+            .get(format!(
+                "{}/xlive/web-room/v2/index/getRoomPlayInfo",
+                self.config.live_api_url
+            ))
+            .query(&[
+                ("room_id", format!("{room_id}").as_str()),
+                ("protocol", "0"),
+                ("format", "0"),
+                ("codec", "0"),
+            ])
+            .send()
+            .await?
+            .json::<BiliResponse<PlayInfo>>()
+            .await?
+            .ok()?;
+
+        if play_info.room_id != room_id {
+            tracing::info!("[{room_id}] real room id: {}", play_info.room_id);
+        }
+
+        Ok(play_info)
+    }
+
+    /// Danmu servers of a room, by its real room id.
+    pub async fn danmu_info(&self, room_id: u32) -> Result<DanmuInfo, Error> {
+        let room_id = room_id.to_string();
+        let params = [
+            ("id", room_id.as_str()),
+            ("type", "0"),
+            ("web_location", "444.8"),
+        ];
+
+        match self.danmu_info_signed(&params).await {
+            // Rejected by risk control, perhaps for outdated keys.
+            Err(Error::BiliResponse(-352, _)) => {
+                self.wbi.invalidate();
+                self.danmu_info_signed(&params).await
+            }
+            r => r,
+        }
+    }
+
+    async fn danmu_info_signed(&self, params: &[(&str, &str)]) -> Result<DanmuInfo, Error> {
+        let wts = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let query = sign(params, &self.wbi_key().await?, wts);
+
+        self.client
+            .get(format!(
+                "{}/xlive/web-room/v1/index/getDanmuInfo",
+                self.config.live_api_url
+            ))
+            .query(&query)
+            .send()
+            .await?
+            .json::<BiliResponse<DanmuInfo>>()
+            .await?
+            .ok()
+    }
+
+    /// The WBI mixin key, fetched from the nav endpoint if not cached.
+    async fn wbi_key(&self) -> Result<String, Error> {
+        if let Some(key) = self.wbi.mixin_key() {
+            return Ok(key);
+        }
+
+        let img = self
+            .user_info()
+            .await?
+            .wbi_img
+            .ok_or(MissingData("wbi_img"))?;
+        Ok(self.wbi.set_keys(&img.img_url, &img.sub_url))
+    }
+
+    /// Sends a danmu mentioning the user `reply_mid`, or nobody if it is 0.
+    pub async fn send_danmu(
+        &self,
+        room_id: u32,
+        message: &str,
+        reply_mid: u64,
+    ) -> Result<(), Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let bili_jct = self.credentials.csrf().ok_or(MissingData("bili_jct"))?;
+
+        let form = reqwest::multipart::Form::new()
+            .text("bubble", "0")
+            .text("msg", message.to_string())
+            .text("color", "5816798")
+            .text("mode", "1")
+            .text("room_type", "0")
+            .text("jumpfrom", "0")
+            .text("reply_mid", reply_mid.to_string())
+            .text("reply_attr", "0")
+            .text("replay_dmid", "")
+            .text("fontsize", "25")
+            .text("rnd", timestamp.to_string())
+            .text("roomid", room_id.to_string())
+            .text("csrf", bili_jct.clone())
+            .text("csrf_token", bili_jct);
+
+        let response: serde_json::Value = self
+            .client
+            .post(format!("{}/msg/send", self.config.live_api_url))
+            .header("Referer", format!("{}/{room_id}", self.config.live_url))
+            .multipart(form)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        check_code(&response)
+    }
+
+    /// Fetches real device identifiers from the SPI endpoint.
+    pub async fn spi(&self) -> Result<Buvid, Error> {
+        #[derive(Deserialize)]
+        struct Spi {
+            b_3: String,
+            b_4: String,
+        }
+
+        let spi = self
+            .client
+            .get(format!("{}/x/frontend/finger/spi", self.config.api_url))
+            .send()
+            .await?
+            .json::<BiliResponse<Spi>>()
+            .await?
+            .ok()?;
+
+        Ok(Buvid {
+            buvid3: spi.b_3,
+            buvid4: spi.b_4,
+            b_nut: b_nut(),
+            uuid: gen_buvid3(),
+        })
+    }
+
+    /// Activates the identifiers in the credentials by reporting a browser
+    /// fingerprint, without which they are soon risk controlled.
+    pub async fn activate_buvid(&self) -> Result<(), Error> {
+        let uuid = self.credentials.get("_uuid").ok_or(MissingData("_uuid"))?;
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        let payload = json!({
+            "3064": 1,
+            "5062": timestamp.to_string(),
+            "03bf": "https://www.bilibili.com/",
+            "39c8": "333.1007.fp.risk",
+            "34f1": "",
+            "d402": "",
+            "654a": "",
+            "6e7c": "1920x1080",
+            "3c43": {
+                "2673": 0,
+                "5766": 24,
+                "6527": 0,
+                "7003": 1,
+                "807e": 1,
+                "b8ce": USER_AGENT,
+                "641c": 0,
+                "07a4": "zh-CN",
+                "1c57": 8,
+                "0bd0": 8,
+                "748e": [1920, 1080],
+                "d61f": [1920, 1040],
+                "fc9d": -480,
+                "6aa9": "Asia/Shanghai",
+                "75b8": 1,
+                "3b21": 1,
+                "8a1c": 0,
+                "d52f": "not available",
+                "adca": "Win32",
+                "80c9": [],
+                "13ab": "",
+                "bfe9": "",
+                "a3c1": [],
+                "6bc5": "",
+                "ed31": 0,
+                "72bd": 0,
+                "097b": 0,
+                "52cd": [0, 0, 0],
+                "a658": [],
+                "d02f": "124.04347527516074"
+            },
+            "54ef": "{}",
+            "8b94": "",
+            "df35": uuid,
+            "07a4": "zh-CN",
+            "5f45": null,
+            "db46": 0
+        });
+
+        let response: serde_json::Value = self
+            .client
+            .post(format!(
+                "{}/x/internal/gaia-gateway/ExClimbWuzhi",
+                self.config.api_url
+            ))
+            .json(&json!({ "payload": payload.to_string() }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        check_code(&response)
+    }
+
+    /// The logged in user, or `is_login: false` if logged out.
+    pub async fn user_info(&self) -> Result<Nav, Error> {
+        self.client
+            .get(format!("{}/x/web-interface/nav", self.config.api_url))
+            .send()
+            .await?
+            .json::<BiliResponse<Nav>>()
+            .await?
+            .ok()
+    }
+}
+
+/// Checks the `code` of responses whose errors still carry a `data` field.
+fn check_code(response: &serde_json::Value) -> Result<(), Error> {
+    match response["code"].as_i64() {
+        Some(0) => Ok(()),
+        code => Err(Error::BiliResponse(
+            code.unwrap_or(-1) as i32,
+            response["message"].as_str().unwrap_or_default().to_string(),
+        )),
+    }
+}
+
+fn default_live_url() -> String {
+    "https://live.bilibili.com".to_string()
+}
+
+fn default_live_api_url() -> String {
+    "https://api.live.bilibili.com".to_string()
+}
+
+fn default_api_url() -> String {
+    "https://api.bilibili.com".to_string()
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_connect_timeout() -> u64 {
+    5
+}
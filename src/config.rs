@@ -0,0 +1,482 @@
+use std::io::ErrorKind;
+use std::path::Path;
+
+use blive_greeting::client::ClientConfig;
+use blive_greeting::notify::{Discord, EventKind, Smtp, Telegram, Webhook};
+use blive_greeting::ntqq::Peer;
+use blive_greeting::{ConnectOptions, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    #[serde(default = "default_rooms")]
+    pub rooms: Vec<u32>,
+    /// Accounts to send danmu as, tried in order.
+    #[serde(default = "default_accounts")]
+    pub accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub credential: CredentialConfig,
+    /// How rooms connect to the danmu servers.
+    #[serde(default)]
+    pub connect: ConnectOptions,
+    /// Fetch real buvids from Bilibili instead of generating them.
+    #[serde(default = "default_fetch_buvid")]
+    pub fetch_buvid: bool,
+    /// HTTP client of each account.
+    #[serde(default)]
+    pub http: ClientConfig,
+    /// File persisting the greeting state of rooms.
+    #[serde(default = "default_state_file")]
+    pub state_file: String,
+    #[serde(default)]
+    pub greeting: GreetingConfig,
+    pub onebot: Option<OneBotConfig>,
+    #[serde(default)]
+    pub relays: Vec<RelayConfig>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    /// Maximum danmu sent per minute across all rooms by automatic replies.
+    #[serde(default = "default_send_rate")]
+    pub send_per_minute: usize,
+    #[serde(default)]
+    pub welcomes: Vec<WelcomeConfig>,
+    #[serde(default)]
+    pub thanks: Vec<ThanksConfig>,
+    #[serde(default)]
+    pub auto_replies: Vec<AutoReplyConfig>,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+}
+
+/// A Bilibili account logged in from a cookie file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountConfig {
+    pub name: String,
+    pub cookies: String,
+    /// Rooms preferring this account, e.g. those of its fan medals.
+    /// Accounts without rooms serve every room as fallback.
+    #[serde(default)]
+    pub rooms: Vec<u32>,
+    /// File persisting the buvid of the account, `buvid_<name>.json` by default.
+    pub buvid: Option<String>,
+}
+
+/// Periodic check of the login of accounts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CredentialConfig {
+    /// Seconds between checks.
+    #[serde(
+        default = "default_credential_interval",
+        deserialize_with = "deserialize_interval"
+    )]
+    pub interval: u64,
+    /// Refresh cookies expiring within this many seconds.
+    #[serde(default = "default_refresh_before")]
+    pub refresh_before: u64,
+}
+
+impl Default for CredentialConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_credential_interval(),
+            refresh_before: default_refresh_before(),
+        }
+    }
+}
+
+/// Greeting sent once per broadcast session.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GreetingConfig {
+    /// Minimum seconds between greetings of different sessions.
+    #[serde(default = "default_greeting_gap")]
+    pub min_gap: u64,
+    /// Seconds to wait after a broadcast starts before greeting.
+    #[serde(default)]
+    pub delay: u64,
+}
+
+impl Default for GreetingConfig {
+    fn default() -> Self {
+        Self {
+            min_gap: default_greeting_gap(),
+            delay: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OneBotConfig {
+    pub endpoint: String,
+    pub event_url: String,
+    #[serde(default)]
+    pub token: String,
+    /// QQ users allowed to issue commands.
+    #[serde(default)]
+    pub admins: Vec<i64>,
+}
+
+/// Forwards danmu and super chats of a room to a QQ group.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayConfig {
+    pub room_id: u32,
+    pub group_id: i64,
+    /// Forward danmu from viewers with a fan medal of at least this level.
+    pub medal_level: Option<u64>,
+    /// Forward danmu containing any of these keywords.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Forward danmu from these users.
+    #[serde(default)]
+    pub uids: Vec<u64>,
+    /// Seconds between batches.
+    #[serde(
+        default = "default_relay_interval",
+        deserialize_with = "deserialize_interval"
+    )]
+    pub interval: u64,
+    /// Maximum danmu lines in one batch; the rest are counted as omitted.
+    /// Super chats past it are sent in the next batch instead.
+    #[serde(default = "default_relay_batch")]
+    pub max_batch: usize,
+    #[serde(default = "default_relay_rate")]
+    pub max_per_minute: usize,
+}
+
+/// Greets viewers entering or following a room.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WelcomeConfig {
+    pub room_id: u32,
+    /// Template for viewers entering the room, with `{uname}` and `{uid}`.
+    pub enter: Option<String>,
+    /// Template for new followers, with `{uname}` and `{uid}`.
+    pub follow: Option<String>,
+    /// Template for guards entering the room, with `{uname}`, `{uid}` and `{guard}`.
+    pub guard: Option<String>,
+    /// Minimum fan medal level of entering viewers to greet.
+    #[serde(default)]
+    pub medal_level: u64,
+    /// Seconds before the same viewer is greeted again.
+    #[serde(default = "default_welcome_cooldown")]
+    pub cooldown: u64,
+    #[serde(default)]
+    pub blocklist: Vec<u64>,
+}
+
+/// Thanks viewers for gifts, guards and super chats in a room.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThanksConfig {
+    pub room_id: u32,
+    /// Template for gifts, with `{uname}`, `{uid}`, `{gift}` and `{num}`.
+    pub gift: Option<String>,
+    /// Template for guards, with `{uname}`, `{uid}`, `{guard}` and `{num}`.
+    pub guard: Option<String>,
+    /// Template for super chats, with `{uname}`, `{uid}`, `{price}` and `{message}`.
+    pub super_chat: Option<String>,
+    /// Seconds without new gifts before a viewer's gifts are thanked together.
+    #[serde(default = "default_thanks_window")]
+    pub window: u64,
+    /// Minimum value of a viewer's gifts in gold coins, where 1000 are 1 CNY.
+    #[serde(default)]
+    pub min_gift_value: u64,
+    /// Minimum price of super chats in CNY.
+    #[serde(default)]
+    pub min_super_chat: u64,
+}
+
+/// Replies to danmu matching the rules in a room.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoReplyConfig {
+    pub room_id: u32,
+    pub rules: Vec<ReplyRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplyRule {
+    #[serde(flatten)]
+    pub pattern: Pattern,
+    /// Template with `{uname}`, `{uid}` and `{content}`.
+    pub reply: String,
+    /// Only reply to viewers with a fan medal of at least this level.
+    pub medal_level: Option<u64>,
+    /// Only reply to these users, or everyone if empty.
+    #[serde(default)]
+    pub uids: Vec<u64>,
+    /// Seconds before the rule replies again.
+    #[serde(default = "default_reply_cooldown")]
+    pub cooldown: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    Exact(String),
+    Contains(String),
+    #[serde(deserialize_with = "deserialize_regex")]
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn is_match(&self, content: &str) -> bool {
+        match self {
+            Pattern::Exact(s) => content.trim() == s,
+            Pattern::Contains(s) => content.contains(s.as_str()),
+            Pattern::Regex(r) => r.is_match(content),
+        }
+    }
+}
+
+/// Posts messages in turn while a room is live.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleConfig {
+    pub room_id: u32,
+    /// Seconds between messages.
+    #[serde(
+        default = "default_schedule_interval",
+        deserialize_with = "deserialize_interval"
+    )]
+    pub interval: u64,
+    pub messages: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotifierConfig {
+    /// Rooms to notify about, or all rooms if empty.
+    #[serde(default)]
+    pub rooms: Vec<u32>,
+    /// Events to notify about, or all events if empty.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    #[serde(flatten)]
+    pub sink: Sink,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Sink {
+    Onebot { peer: Peer },
+    Webhook(Webhook),
+    Telegram(Telegram),
+    Discord(Discord),
+    Smtp(Smtp),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rooms: default_rooms(),
+            accounts: default_accounts(),
+            credential: CredentialConfig::default(),
+            connect: ConnectOptions::default(),
+            fetch_buvid: default_fetch_buvid(),
+            http: ClientConfig::default(),
+            state_file: default_state_file(),
+            greeting: GreetingConfig::default(),
+            onebot: None,
+            relays: Vec::new(),
+            notifiers: Vec::new(),
+            send_per_minute: default_send_rate(),
+            welcomes: Vec::new(),
+            thanks: Vec::new(),
+            auto_replies: Vec::new(),
+            schedules: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(s) => Ok(serde_json::from_str(&s)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> std::result::Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s).map_err(serde::de::Error::custom)
+}
+
+/// Seconds between timed tasks, which must be positive.
+fn deserialize_interval<'de, D>(deserializer: D) -> std::result::Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u64::deserialize(deserializer)? {
+        0 => Err(serde::de::Error::custom(
+            "interval must be at least 1 second",
+        )),
+        secs => Ok(secs),
+    }
+}
+
+fn default_rooms() -> Vec<u32> {
+    vec![4588774, 21669627, 33989]
+}
+
+fn default_accounts() -> Vec<AccountConfig> {
+    vec![AccountConfig {
+        name: "default".to_string(),
+        cookies: "cookies.json".to_string(),
+        rooms: Vec::new(),
+        buvid: None,
+    }]
+}
+
+fn default_fetch_buvid() -> bool {
+    true
+}
+
+fn default_credential_interval() -> u64 {
+    6 * 3600
+}
+
+fn default_refresh_before() -> u64 {
+    7 * 86400
+}
+
+fn default_state_file() -> String {
+    "state.json".to_string()
+}
+
+fn default_greeting_gap() -> u64 {
+    10
+}
+
+fn default_relay_interval() -> u64 {
+    10
+}
+
+fn default_relay_batch() -> usize {
+    20
+}
+
+fn default_relay_rate() -> usize {
+    6
+}
+
+fn default_send_rate() -> usize {
+    10
+}
+
+fn default_welcome_cooldown() -> u64 {
+    3600
+}
+
+fn default_thanks_window() -> u64 {
+    5
+}
+
+fn default_reply_cooldown() -> u64 {
+    60
+}
+
+fn default_schedule_interval() -> u64 {
+    900
+}
+
+#[cfg(test)]
+mod tests {
+    use blive_greeting::{ProtoVer, Transport};
+    use serde_json::json;
+
+    use super::*;
+
+    fn rule(pattern: serde_json::Value) -> serde_json::Result<ReplyRule> {
+        let mut rule = json!({ "reply": "{uname}" });
+        rule.as_object_mut()
+            .unwrap()
+            .extend(pattern.as_object().unwrap().clone());
+        serde_json::from_value(rule)
+    }
+
+    #[test]
+    fn parses_reply_patterns() {
+        let exact = rule(json!({ "exact": "签到" })).unwrap();
+        assert!(exact.pattern.is_match(" 签到 "));
+        assert!(!exact.pattern.is_match("签到了"));
+        assert_eq!(exact.cooldown, default_reply_cooldown());
+
+        let contains = rule(json!({ "contains": "晚上好" })).unwrap();
+        assert!(contains.pattern.is_match("主播晚上好"));
+
+        let regex = rule(json!({ "regex": "^(早|晚)安$" })).unwrap();
+        assert!(regex.pattern.is_match("晚安"));
+        assert!(!regex.pattern.is_match("晚安啦"));
+
+        assert!(rule(json!({ "regex": "(" })).is_err());
+        assert!(rule(json!({})).is_err());
+    }
+
+    #[test]
+    fn parses_notifier_sinks() {
+        let notifiers: Vec<NotifierConfig> = serde_json::from_value(json!([
+            { "type": "onebot", "peer": { "group": 123 }, "rooms": [1], "events": ["live"] },
+            { "type": "webhook", "url": "http://127.0.0.1/hook" },
+            { "type": "telegram", "token": "t", "chat_id": 42 },
+            { "type": "smtp", "host": "localhost", "from": "a@b.c", "to": ["d@e.f"] }
+        ]))
+        .unwrap();
+
+        assert!(matches!(
+            &notifiers[0],
+            NotifierConfig {
+                rooms,
+                events,
+                sink: Sink::Onebot { peer: Peer::Group(123) },
+            } if rooms == &[1] && events == &[EventKind::Live]
+        ));
+        assert!(matches!(&notifiers[1].sink, Sink::Webhook(w) if w.body.is_none()));
+        assert!(notifiers[1].rooms.is_empty() && notifiers[1].events.is_empty());
+        assert!(
+            matches!(&notifiers[2].sink, Sink::Telegram(t) if t.api == "https://api.telegram.org")
+        );
+        assert!(matches!(&notifiers[3].sink, Sink::Smtp(s) if s.tls && s.port.is_none()));
+
+        assert!(serde_json::from_value::<NotifierConfig>(json!({ "type": "pager" })).is_err());
+    }
+
+    #[test]
+    fn parses_connect_options() {
+        let config: Config = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(config.connect.transport, Transport::Wss);
+        assert_eq!(config.connect.proto_ver, ProtoVer::Brotli);
+        assert!(!config.connect.guest);
+
+        let config: Config = serde_json::from_value(json!({
+            "connect": { "transport": "tcp", "proto_ver": 2, "guest": true }
+        }))
+        .unwrap();
+        assert_eq!(config.connect.transport, Transport::Tcp);
+        assert_eq!(config.connect.proto_ver, ProtoVer::Zlib);
+        assert!(config.connect.guest);
+
+        let connect = json!({ "connect": { "transport": "quic" } });
+        assert!(serde_json::from_value::<Config>(connect).is_err());
+        let connect = json!({ "connect": { "proto_ver": 7 } });
+        assert!(serde_json::from_value::<Config>(connect).is_err());
+    }
+
+    #[test]
+    fn rejects_zero_interval() {
+        let relay = json!({ "relays": [{ "room_id": 1, "group_id": 2, "interval": 0 }] });
+        assert!(serde_json::from_value::<Config>(relay).is_err());
+
+        let schedule = json!({ "schedules": [{ "room_id": 1, "interval": 0, "messages": [] }] });
+        assert!(serde_json::from_value::<Config>(schedule).is_err());
+
+        let credential = json!({ "credential": { "interval": 0 } });
+        assert!(serde_json::from_value::<Config>(credential).is_err());
+
+        let relay = json!({ "relays": [{ "room_id": 1, "group_id": 2, "interval": 1 }] });
+        assert_eq!(
+            serde_json::from_value::<Config>(relay).unwrap().relays[0].interval,
+            1
+        );
+    }
+}
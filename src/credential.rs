@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use biliup::credential::LoginInfo;
+use md5::{Digest, Md5};
+use reqwest::cookie::CookieStore;
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde_json::Value;
+
+use crate::info::BiliResponse;
+use crate::Buvid;
+use crate::Error::{self, MissingData};
+
+/// App key and secret of the TV client, which `biliup login` logs in as.
+const APP_KEY: &str = "4409e2ce8ffd12b8";
+const APP_SEC: &str = "59b43e04ad6965f34319062b478f83dd";
+
+/// Cookies of a Bilibili account. Clones share the cookies, so updates,
+/// including those set by responses, are seen by every client built from them.
+#[derive(Debug, Clone, Default)]
+pub struct BiliCredentials {
+    jar: Arc<CookieJar>,
+}
+
+impl BiliCredentials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_login_info(login_info: &LoginInfo) -> Self {
+        let credentials = Self::new();
+        credentials.update(login_info);
+        credentials
+    }
+
+    /// Sets the cookies of a login, keeping the others such as `buvid3`.
+    pub fn update(&self, login_info: &LoginInfo) {
+        let cookies = login_info
+            .cookie_info
+            .get("cookies")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten();
+
+        let mut jar = self.jar.cookies.write().unwrap();
+        for cookie in cookies {
+            if let (Some(name), Some(value)) = (cookie["name"].as_str(), cookie["value"].as_str()) {
+                let expires = cookie["expires"]
+                    .as_u64()
+                    .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+                jar.insert(name.to_string(), Cookie::new(value, expires));
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let jar = self.jar.cookies.read().unwrap();
+        let cookie = jar.get(name)?;
+        (!cookie.is_expired(SystemTime::now())).then(|| cookie.value.clone())
+    }
+
+    pub fn set(&self, name: &str, value: &str) {
+        let mut jar = self.jar.cookies.write().unwrap();
+        jar.insert(name.to_string(), Cookie::new(value, None));
+    }
+
+    /// `DedeUserID`, the uid of the account.
+    pub fn uid(&self) -> Option<u64> {
+        self.get("DedeUserID")?.parse().ok()
+    }
+
+    /// `bili_jct`, the CSRF token.
+    pub fn csrf(&self) -> Option<String> {
+        self.get("bili_jct")
+    }
+
+    pub fn buvid3(&self) -> Option<String> {
+        self.get("buvid3")
+    }
+
+    /// Sets the device identifier cookies.
+    pub fn set_buvid(&self, buvid: &Buvid) {
+        self.set("buvid3", &buvid.buvid3);
+        if !buvid.buvid4.is_empty() {
+            self.set("buvid4", &buvid.buvid4);
+        }
+        self.set("b_nut", &buvid.b_nut);
+        self.set("_uuid", &buvid.uuid);
+    }
+
+    /// The cookie store to build clients with, sending and accepting the
+    /// cookies of bilibili.com and of the hosts of `urls` only.
+    pub fn cookie_store(&self, urls: &[&str]) -> Arc<impl CookieStore + 'static> {
+        let hosts = urls
+            .iter()
+            .filter_map(|url| Some(Url::parse(url).ok()?.host_str()?.to_string()))
+            .collect();
+
+        Arc::new(ScopedJar {
+            jar: self.jar.clone(),
+            hosts,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct CookieJar {
+    cookies: RwLock<HashMap<String, Cookie>>,
+}
+
+#[derive(Debug)]
+struct Cookie {
+    value: String,
+    /// `None` for a session cookie.
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    fn new(value: &str, expires: Option<SystemTime>) -> Self {
+        Self {
+            value: value.to_string(),
+            expires,
+        }
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Parses a `Set-Cookie` header into its name and cookie. `Max-Age`
+    /// takes precedence over `Expires`, and unknown attributes are ignored.
+    fn parse(header: &str, now: SystemTime) -> Option<(&str, Self)> {
+        let mut attributes = header.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+
+        let mut max_age = None;
+        let mut expires = None;
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "max-age" => {
+                    max_age = value
+                        .parse::<i64>()
+                        .ok()
+                        .map(|secs| match u64::try_from(secs) {
+                            Ok(secs) if secs > 0 => now + Duration::from_secs(secs),
+                            _ => SystemTime::UNIX_EPOCH,
+                        })
+                }
+                "expires" => expires = httpdate::parse_http_date(value).ok(),
+                _ => {}
+            }
+        }
+
+        Some((name.trim(), Self::new(value.trim(), max_age.or(expires))))
+    }
+}
+
+/// The jar as seen by one client.
+#[derive(Debug)]
+struct ScopedJar {
+    jar: Arc<CookieJar>,
+    /// Hosts besides bilibili.com, such as those of base URLs for testing.
+    hosts: Vec<String>,
+}
+
+impl ScopedJar {
+    fn accepts(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|host| {
+            host == "bilibili.com"
+                || host.ends_with(".bilibili.com")
+                || self.hosts.iter().any(|h| h == host)
+        })
+    }
+}
+
+impl CookieStore for ScopedJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        if !self.accepts(url) {
+            return;
+        }
+
+        let now = SystemTime::now();
+        let mut cookies = self.jar.cookies.write().unwrap();
+        for header in cookie_headers {
+            match header.to_str().ok().and_then(|h| Cookie::parse(h, now)) {
+                // Servers delete a cookie by expiring it.
+                Some((name, cookie)) if cookie.is_expired(now) => {
+                    cookies.remove(name);
+                }
+                Some((name, cookie)) => {
+                    cookies.insert(name.to_string(), cookie);
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        if !self.accepts(url) {
+            return None;
+        }
+
+        let now = SystemTime::now();
+        let cookies = self.jar.cookies.read().unwrap();
+        let header = cookies
+            .iter()
+            .filter(|(_, cookie)| !cookie.is_expired(now))
+            .map(|(name, cookie)| format!("{name}={}", cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+/// Unix time when the `SESSDATA` cookie of a login expires.
+pub fn expires(login: &Value) -> Option<i64> {
+    login["cookie_info"]["cookies"]
+        .as_array()?
+        .iter()
+        .find(|c| c["name"] == "SESSDATA")?["expires"]
+        .as_i64()
+}
+
+/// Renews the tokens and cookies of a login, as read from a cookie file,
+/// with its refresh token.
+pub async fn refresh(login: &Value) -> Result<Value, Error> {
+    let access_token = login["token_info"]["access_token"]
+        .as_str()
+        .ok_or(MissingData("access_token"))?;
+    let refresh_token = login["token_info"]["refresh_token"]
+        .as_str()
+        .ok_or(MissingData("refresh_token"))?;
+
+    let ts = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // Parameters are signed in alphabetical order.
+    let query = format!(
+        "access_key={access_token}&actionKey=appkey&appkey={APP_KEY}&refresh_token={refresh_token}&ts={ts}"
+    );
+    let sign = format!("{:x}", Md5::digest(format!("{query}{APP_SEC}")));
+
+    let data = reqwest::Client::new()
+        .post(format!(
+            "https://passport.bilibili.com/x/passport-login/oauth2/refresh_token?{query}&sign={sign}"
+        ))
+        .send()
+        .await?
+        .json::<BiliResponse<Value>>()
+        .await?
+        .ok()?;
+
+    if !data["token_info"].is_object() || !data["cookie_info"].is_object() {
+        return Err(MissingData("token_info"));
+    }
+
+    let mut login = login.clone();
+    login["token_info"] = data["token_info"].clone();
+    login["cookie_info"] = data["cookie_info"].clone();
+    Ok(login)
+}
+
+/// Writes a login back to its cookie file, or any JSON file, replacing it
+/// atomically.
+pub async fn save(path: impl AsRef<Path>, login: &Value) -> Result<(), Error> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(login)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_cookies(jar: &impl CookieStore, url: &str, headers: &[&str]) {
+        let headers: Vec<_> = headers
+            .iter()
+            .map(|h| HeaderValue::from_str(h).unwrap())
+            .collect();
+        jar.set_cookies(&mut headers.iter(), &Url::parse(url).unwrap());
+    }
+
+    #[test]
+    fn parses_attributes() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let (name, cookie) = Cookie::parse(
+            "SESSDATA=a%2Cb; Path=/; Domain=bilibili.com; Expires=Wed, 21 Oct 2099 07:28:00 GMT; HttpOnly; Secure",
+            now,
+        )
+        .unwrap();
+        assert_eq!((name, cookie.value.as_str()), ("SESSDATA", "a%2Cb"));
+        assert_eq!(
+            cookie.expires,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(4_096_250_880))
+        );
+
+        let (_, cookie) = Cookie::parse(
+            "bili_jct=x; max-age=60; Expires=Wed, 21 Oct 2099 07:28:00 GMT",
+            now,
+        )
+        .unwrap();
+        assert_eq!(cookie.expires, Some(now + Duration::from_secs(60)));
+
+        let (_, cookie) = Cookie::parse("buvid3=x; Path=/", now).unwrap();
+        assert_eq!(cookie.expires, None);
+    }
+
+    #[test]
+    fn removes_expired_cookies() {
+        let credentials = BiliCredentials::new();
+        let jar = credentials.cookie_store(&[]);
+        let url = "https://api.bilibili.com/x/web-interface/nav";
+        set_cookies(
+            &*jar,
+            url,
+            &[
+                "SESSDATA=a; Path=/; Expires=Wed, 21 Oct 2099 07:28:00 GMT; HttpOnly",
+                "bili_jct=b; Max-Age=3600",
+                "DedeUserID=42; Path=/",
+            ],
+        );
+        set_cookies(
+            &*jar,
+            url,
+            &[
+                "SESSDATA=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+                "bili_jct=; Max-Age=0",
+            ],
+        );
+
+        assert_eq!(credentials.get("SESSDATA"), None);
+        assert_eq!(credentials.csrf(), None);
+        assert_eq!(credentials.uid(), Some(42));
+        let url = Url::parse("https://live.bilibili.com/").unwrap();
+        assert_eq!(jar.cookies(&url).unwrap(), "DedeUserID=42");
+    }
+
+    #[test]
+    fn scopes_cookies_to_hosts() {
+        let credentials = BiliCredentials::new();
+        credentials.set("SESSDATA", "a");
+        let jar = credentials.cookie_store(&["http://127.0.0.1:8080", "https://api.bilibili.com"]);
+
+        let local = Url::parse("http://127.0.0.1:9000/x/web-interface/nav").unwrap();
+        assert_eq!(jar.cookies(&local).unwrap(), "SESSDATA=a");
+        set_cookies(&*jar, local.as_str(), &["bili_jct=b"]);
+        assert_eq!(credentials.csrf().as_deref(), Some("b"));
+
+        let other = Url::parse("https://example.com/").unwrap();
+        assert_eq!(jar.cookies(&other), None);
+        set_cookies(&*jar, other.as_str(), &["SESSDATA=c"]);
+        assert_eq!(credentials.get("SESSDATA").as_deref(), Some("a"));
+
+        // Other clients of the account don't send them to the host.
+        assert_eq!(credentials.cookie_store(&[]).cookies(&local), None);
+    }
+}
@@ -1,42 +1,20 @@
-use std::{collections::HashMap, time::SystemTime};
+use std::time::SystemTime;
 
-use crate::info::bili_client;
-use crate::Error::{self, MissingData};
+use crate::client::BiliClient;
+use crate::Error;
 
-pub async fn send_greeting(cookies: &HashMap<&str, &str>, room_id: u32) -> Result<(), Error> {
+pub async fn send_greeting(client: &BiliClient, room_id: u32) -> Result<(), Error> {
+    client.send_danmu(room_id, greeting(), 0).await
+}
+
+/// The greeting for the current time of day.
+pub fn greeting() -> &'static str {
     let timestamp = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
 
-    let bili_jct = cookies.get("bili_jct").ok_or(MissingData("bili_jct"))?;
-
-    let client = bili_client(cookies)?;
-
-    let form = reqwest::multipart::Form::new()
-        .text("bubble", "0")
-        .text("msg", greeting_word(timestamp))
-        .text("color", "5816798")
-        .text("mode", "1")
-        .text("room_type", "0")
-        .text("jumpfrom", "0")
-        .text("reply_mid", "0")
-        .text("reply_attr", "0")
-        .text("replay_dmid", "")
-        .text("fontsize", "25")
-        .text("rnd", timestamp.to_string())
-        .text("roomid", room_id.to_string())
-        .text("csrf", bili_jct.to_string())
-        .text("csrf_token", bili_jct.to_string());
-
-    client
-        .post("https://api.live.bilibili.com/msg/send")
-        .header("Referer", format!("https://live.bilibili.com/{room_id}"))
-        .multipart(form)
-        .send()
-        .await?;
-
-    Ok(())
+    greeting_word(timestamp)
 }
 
 fn greeting_word(timestamp: u64) -> &'static str {
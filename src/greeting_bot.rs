@@ -1,23 +1,82 @@
-use std::{collections::HashMap, time::SystemTime};
+use std::{time::Duration, time::SystemTime};
 
-use blive_greeting::{LiveMessage, LiveSubHandler, Result};
-use tokio::sync::Mutex;
+use blive_greeting::{LiveMessage, LiveSubHandler, LiveTracker, Result};
+use tokio::sync::watch;
 use tracing::{debug, error, info};
 
-use blive_greeting::danmu::send_greeting;
+use blive_greeting::danmu::greeting;
+
+use crate::config::GreetingConfig;
+use crate::sender::DanmuSender;
+use crate::store::{RoomState, StateStore};
 
 pub struct LiveGreetingBot<'a> {
     room_id: u32,
-    cookies: &'a HashMap<&'a str, &'a str>,
-    last_greeting: Mutex<SystemTime>,
+    config: &'a GreetingConfig,
+    store: &'a StateStore,
+    /// `live_time` of the session waiting to be greeted, owned by the room so
+    /// that it survives reconnects.
+    pending: &'a watch::Sender<Option<Option<i64>>>,
+    /// Learns the `live_time` of a session greeted on a `LIVE` without it.
+    tracker: &'a LiveTracker,
 }
 
 impl<'a> LiveGreetingBot<'a> {
-    pub fn new(room_id: u32, cookies: &'a HashMap<&str, &str>) -> Self {
+    pub fn new(
+        room_id: u32,
+        config: &'a GreetingConfig,
+        store: &'a StateStore,
+        pending: &'a watch::Sender<Option<Option<i64>>>,
+        tracker: &'a LiveTracker,
+    ) -> Self {
         Self {
             room_id,
-            cookies,
-            last_greeting: Mutex::new(SystemTime::now()),
+            config,
+            store,
+            pending,
+            tracker,
+        }
+    }
+
+    /// Greets a pending session after `delay` seconds, unless it ends before.
+    pub async fn run(&self, sender: &DanmuSender<'_>) {
+        let delay = Duration::from_secs(self.config.delay);
+        let mut pending = self.pending.subscribe();
+        loop {
+            let live_time = match pending.wait_for(Option::is_some).await {
+                Ok(live_time) => live_time.flatten(),
+                Err(_) => return,
+            };
+
+            let ended = async {
+                let _ = pending.wait_for(Option::is_none).await;
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => self.greet(live_time, sender).await,
+                _ = ended => debug!("[{}] session ended before greeting", self.room_id),
+            }
+        }
+    }
+
+    async fn greet(&self, live_time: Option<i64>, sender: &DanmuSender<'_>) {
+        let result = sender.post(greeting(), 0).await;
+        self.pending.send_replace(None);
+
+        if let Err(e) = result {
+            error!("[{}] send greeting error: {e}", self.room_id);
+            return;
+        }
+        info!("[{}] greeting sent", self.room_id);
+
+        // Only a sent greeting counts for the session and `min_gap`. A timed
+        // `LIVE` after a bare one only reaches the tracker.
+        let state = RoomState {
+            last_greeting: unix_now(),
+            live_time: live_time.or_else(|| self.tracker.live_time()),
+        };
+        if let Err(e) = self.store.set(self.room_id, state).await {
+            error!("[{}] failed to save state: {e}", self.room_id);
         }
     }
 }
@@ -28,32 +87,45 @@ impl<'a> LiveSubHandler for LiveGreetingBot<'a> {
     }
 
     async fn handle_message(&self, message: &LiveMessage) -> Result<()> {
-        match message {
-            LiveMessage::Live => {
-                let duration = {
-                    let mut last = self.last_greeting.lock().await;
-                    let now = SystemTime::now();
-                    let duration = now.duration_since(*last).unwrap().as_secs();
-                    *last = now;
-                    duration
-                };
-
-                if duration < 10 {
-                    debug!("[{}] debounce greeting within {duration}s", self.room_id);
-                    return Ok(());
-                }
-
-                match send_greeting(self.cookies, self.room_id).await {
-                    Ok(_) => info!("[{}] greeting sent", self.room_id),
-                    Err(e) => error!("[{}] send greeting error: {e}", self.room_id),
-                }
-
-                Ok(())
+        let live_time = match message {
+            LiveMessage::Live { live_time } => *live_time,
+            LiveMessage::MissedLive { live_time } => Some(*live_time),
+            LiveMessage::Preparing => {
+                self.pending.send_replace(None);
+                return Ok(());
             }
             _ => {
                 debug!("[{}] received {message:?}", self.room_id);
-                Ok(())
+                return Ok(());
             }
+        };
+
+        if self.pending.borrow().is_some() {
+            debug!("[{}] greeting already pending", self.room_id);
+            return Ok(());
         }
+
+        let state = self.store.get(self.room_id).await;
+        if live_time.is_some() && live_time == state.live_time {
+            debug!("[{}] already greeted session {live_time:?}", self.room_id);
+            return Ok(());
+        }
+
+        let duration = unix_now().saturating_sub(state.last_greeting);
+        if duration < self.config.min_gap {
+            debug!("[{}] last greeting only {duration}s ago", self.room_id);
+            return Ok(());
+        }
+
+        self.pending.send_replace(Some(live_time));
+
+        Ok(())
     }
 }
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
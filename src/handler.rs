@@ -4,13 +4,18 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{debug, info};
 
-use crate::{sub::SubReply, Error, Result};
+use crate::{info::PlayInfo, proto, sub::SubReply, Error, Result};
 
 pub trait LiveSubHandler {
     fn get_room_id(&self) -> u32;
 
     fn handle_message(&self, message: &LiveMessage) -> impl Future<Output = Result<()>> + Send;
 
+    /// Called with the room info fetched on each (re)connect.
+    fn handle_room_info(&self, _play_info: &PlayInfo) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
     fn handle_reply(&self, reply: SubReply) -> impl Future<Output = Result<()>> + Send
     where
         Self: Sync,
@@ -40,26 +45,53 @@ pub trait LiveSubHandler {
                         return Err(Error::Handler(self.get_room_id(), "auth"));
                     }
                 }
+                SubReply::Unknown(header, _) => {
+                    debug!("[{}] unknown packet {header:?}", self.get_room_id());
+                }
             }
             Ok(())
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-#[serde(tag = "cmd", content = "data")]
+impl<H: LiveSubHandler + Sync> LiveSubHandler for &H {
+    fn get_room_id(&self) -> u32 {
+        (**self).get_room_id()
+    }
+
+    fn handle_message(&self, message: &LiveMessage) -> impl Future<Output = Result<()>> + Send {
+        (**self).handle_message(message)
+    }
+
+    fn handle_room_info(&self, play_info: &PlayInfo) -> impl Future<Output = Result<()>> + Send {
+        (**self).handle_room_info(play_info)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiveMessage {
-    #[serde(rename = "LIVE")]
-    Live,
-    #[serde(rename = "PREPARING")]
+    Live {
+        live_time: Option<i64>,
+    },
+    /// Synthesized when the room info shows a broadcast session started while
+    /// disconnected, whose `LIVE` messages were missed.
+    MissedLive {
+        live_time: i64,
+    },
     Preparing,
-    #[serde(rename = "DANMU_MSG")]
     Danmu(DanmuMessage),
-    #[serde(rename = "INTERACT_WORD")]
-    Interact,
-    #[serde(rename = "ROOM_CHANGE")]
+    Interact(InteractWord),
+    EntryEffect(EntryEffect),
     RoomChange(RoomChangeData),
-    #[serde(untagged)]
+    SuperChat(SuperChatMessage),
+    Gift(GiftMessage),
+    Combo(ComboMessage),
+    GuardBuy(GuardBuyMessage),
+    OnlineRank(OnlineRank),
+    /// Sent to guests, whose danmu show masked usernames and no uid.
+    LogInNotice {
+        message: String,
+    },
     Other(Value),
 }
 
@@ -72,6 +104,47 @@ pub struct DanmuMessage {
     pub ts: u64,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InteractWord {
+    pub uid: u64,
+    pub uname: String,
+    pub kind: InteractKind,
+    pub medal: Option<FanMedal>,
+    pub ts: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum InteractKind {
+    Enter,
+    Follow,
+    Share,
+    SpecialFollow,
+    MutualFollow,
+    Unknown(u64),
+}
+
+impl From<u64> for InteractKind {
+    fn from(msg_type: u64) -> Self {
+        match msg_type {
+            1 => InteractKind::Enter,
+            2 => InteractKind::Follow,
+            3 => InteractKind::Share,
+            4 => InteractKind::SpecialFollow,
+            5 => InteractKind::MutualFollow,
+            t => InteractKind::Unknown(t),
+        }
+    }
+}
+
+/// Entrance effect shown when a guard enters the room.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryEffect {
+    pub uid: u64,
+    pub uname: String,
+    /// 1 for 总督, 2 for 提督 and 3 for 舰长.
+    pub guard_level: u64,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RoomChangeData {
     pub area_id: u64,
@@ -81,13 +154,86 @@ pub struct RoomChangeData {
     pub title: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SuperChatMessage {
+    pub id: u64,
+    pub uid: u64,
+    pub uname: String,
+    pub price: u64,
+    pub message: String,
+    pub ts: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GiftMessage {
+    pub uid: u64,
+    pub uname: String,
+    #[serde(rename = "giftId")]
+    pub gift_id: u64,
+    #[serde(rename = "giftName")]
+    pub gift_name: String,
+    pub num: u64,
+    /// `gold` for paid gifts, `silver` for free gifts.
+    pub coin_type: String,
+    /// Value of all gifts in coins, where 1000 gold coins are 1 CNY.
+    pub total_coin: u64,
+    #[serde(rename = "timestamp")]
+    pub ts: u64,
+}
+
+/// Summary of a gift combo, sent besides the `SEND_GIFT` of each gift.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComboMessage {
+    pub uid: u64,
+    pub uname: String,
+    pub gift_id: u64,
+    pub gift_name: String,
+    pub total_num: u64,
+    pub combo_total_coin: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardBuyMessage {
+    pub uid: u64,
+    #[serde(rename = "username")]
+    pub uname: String,
+    /// 1 for 总督, 2 for 提督 and 3 for 舰长.
+    pub guard_level: u64,
+    pub gift_name: String,
+    pub num: u64,
+    pub price: u64,
+    #[serde(rename = "start_time")]
+    pub ts: u64,
+}
+
+/// Top viewers by contribution in the session, sent periodically.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnlineRank {
+    pub rank_type: String,
+    pub list: Vec<RankUser>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankUser {
+    pub uid: u64,
+    pub uname: String,
+    pub rank: u64,
+    pub score: String,
+    /// 0 if not a guard, otherwise as in `GuardBuyMessage`.
+    #[serde(default)]
+    pub guard_level: u64,
+}
+
+/// Fields are `None` when the message, such as a protobuf one, lacks them.
 #[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FanMedal {
     pub level: u64,
     pub name: String,
-    pub target_name: String,
-    pub room_id: u64,
+    /// Name of the streamer.
+    pub target_name: Option<String>,
+    pub room_id: Option<u64>,
+    /// Uid of the streamer.
     pub target_id: u64,
 }
 
@@ -98,9 +244,11 @@ impl<'de> Deserialize<'de> for DanmuMessage {
     {
         let root: Value = Deserialize::deserialize(deserializer)?;
 
-        let info = root["info"]
-            .as_array()
-            .ok_or(serde::de::Error::custom("cannot parse array `info`"))?;
+        // Indexing the `Value` rather than the array, as `info` may be short.
+        let info = &root["info"];
+        if !info.is_array() {
+            return Err(serde::de::Error::custom("cannot parse array `info`"));
+        }
 
         let content = info[1]
             .as_str()
@@ -132,6 +280,192 @@ impl<'de> Deserialize<'de> for DanmuMessage {
     }
 }
 
+impl<'de> Deserialize<'de> for LiveMessage {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let root: Value = Deserialize::deserialize(deserializer)?;
+        let data = &root["data"];
+
+        // Messages which fail to parse are kept as `Other`.
+        let message = match root["cmd"].as_str() {
+            Some("LIVE") => Some(LiveMessage::Live {
+                live_time: root["live_time"].as_i64(),
+            }),
+            Some("PREPARING") => Some(LiveMessage::Preparing),
+            Some("DANMU_MSG") => DanmuMessage::deserialize(&root)
+                .ok()
+                .or_else(|| root["dm_v2"].as_str().and_then(proto::danmu))
+                .map(LiveMessage::Danmu),
+            Some("INTERACT_WORD") => InteractWord::deserialize(data)
+                .ok()
+                .map(LiveMessage::Interact),
+            Some("INTERACT_WORD_V2") => data["pb"]
+                .as_str()
+                .and_then(proto::interact_word)
+                .map(LiveMessage::Interact),
+            Some("ENTRY_EFFECT") => EntryEffect::deserialize(data)
+                .ok()
+                .map(LiveMessage::EntryEffect),
+            Some("ROOM_CHANGE") => RoomChangeData::deserialize(data)
+                .ok()
+                .map(LiveMessage::RoomChange),
+            Some("SUPER_CHAT_MESSAGE") => SuperChatMessage::deserialize(data)
+                .ok()
+                .map(LiveMessage::SuperChat),
+            Some("SEND_GIFT") => GiftMessage::deserialize(data).ok().map(LiveMessage::Gift),
+            Some("COMBO_SEND") => ComboMessage::deserialize(data).ok().map(LiveMessage::Combo),
+            Some("GUARD_BUY") => GuardBuyMessage::deserialize(data)
+                .ok()
+                .map(LiveMessage::GuardBuy),
+            Some("ONLINE_RANK_V2") => OnlineRank::deserialize(data)
+                .ok()
+                .map(LiveMessage::OnlineRank),
+            Some("ONLINE_RANK_V3") => data["pb"]
+                .as_str()
+                .and_then(proto::online_rank)
+                .map(LiveMessage::OnlineRank),
+            Some("LOG_IN_NOTICE") => {
+                data["notice_msg"]
+                    .as_str()
+                    .map(|message| LiveMessage::LogInNotice {
+                        message: message.to_string(),
+                    })
+            }
+            _ => None,
+        };
+
+        Ok(message.unwrap_or(LiveMessage::Other(root)))
+    }
+}
+
+impl<'de> Deserialize<'de> for InteractWord {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let root: Value = Deserialize::deserialize(deserializer)?;
+
+        let uid = root["uid"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `uid`"))?;
+
+        let uname = root["uname"]
+            .as_str()
+            .ok_or(serde::de::Error::custom("cannot parse str `uname`"))?
+            .to_string();
+
+        let kind = root["msg_type"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `msg_type`"))?
+            .into();
+
+        let fans_medal = &root["fans_medal"];
+        let medal = match fans_medal["medal_level"].as_u64() {
+            Some(level) if level > 0 => Some(FanMedal {
+                level,
+                name: fans_medal["medal_name"].as_str().unwrap_or("").to_string(),
+                target_name: None,
+                room_id: fans_medal["anchor_roomid"].as_u64(),
+                target_id: fans_medal["target_id"].as_u64().unwrap_or(0),
+            }),
+            _ => None,
+        };
+
+        let ts = root["timestamp"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `timestamp`"))?;
+
+        Ok(Self {
+            uid,
+            uname,
+            kind,
+            medal,
+            ts,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryEffect {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let root: Value = Deserialize::deserialize(deserializer)?;
+
+        let uid = root["uid"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `uid`"))?;
+
+        // e.g. "欢迎舰长 <%uname%> 进入直播间"
+        let uname = root["copy_writing"]
+            .as_str()
+            .and_then(|s| s.split_once("<%"))
+            .and_then(|(_, s)| s.split_once("%>"))
+            .map(|(s, _)| s.to_string())
+            .ok_or(serde::de::Error::custom("cannot parse str `copy_writing`"))?;
+
+        let guard_level = root["privilege_type"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom(
+                "cannot parse u64 `privilege_type`",
+            ))?;
+
+        Ok(Self {
+            uid,
+            uname,
+            guard_level,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for SuperChatMessage {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let root: Value = Deserialize::deserialize(deserializer)?;
+
+        let id = root["id"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `id`"))?;
+
+        let uid = root["uid"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `uid`"))?;
+
+        let uname = root["user_info"]["uname"]
+            .as_str()
+            .ok_or(serde::de::Error::custom(
+                "cannot parse str `user_info.uname`",
+            ))?
+            .to_string();
+
+        let price = root["price"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `price`"))?;
+
+        let message = root["message"]
+            .as_str()
+            .ok_or(serde::de::Error::custom("cannot parse str `message`"))?
+            .to_string();
+
+        let ts = root["start_time"]
+            .as_u64()
+            .ok_or(serde::de::Error::custom("cannot parse u64 `start_time`"))?;
+
+        Ok(Self {
+            id,
+            uid,
+            uname,
+            price,
+            message,
+            ts,
+        })
+    }
+}
+
 impl<'de> Deserialize<'de> for FanMedal {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -148,14 +482,8 @@ impl<'de> Deserialize<'de> for FanMedal {
             .ok_or(serde::de::Error::custom("cannot parse str `[1]`"))?
             .to_string();
 
-        let target_name = root[2]
-            .as_str()
-            .ok_or(serde::de::Error::custom("cannot parse str `[2]`"))?
-            .to_string();
-
-        let room_id = root[3]
-            .as_u64()
-            .ok_or(serde::de::Error::custom("cannot parse u64 `[3]`"))?;
+        let target_name = root[2].as_str().map(str::to_string);
+        let room_id = root[3].as_u64();
 
         let target_id = root[12]
             .as_u64()
@@ -1,7 +1,3 @@
-use std::collections::HashMap;
-
-use biliup::credential::LoginInfo;
-use reqwest::header::{HeaderValue, COOKIE};
 use serde::Deserialize;
 
 use crate::Error;
@@ -26,6 +22,28 @@ pub struct DanmuHost {
 pub struct PlayInfo {
     pub room_id: u32,
     pub short_id: u32,
+    /// 0 for offline, 1 for live and 2 for playing videos in rotation.
+    pub live_status: u8,
+    /// Start time of the current broadcast session, or 0 if offline.
+    pub live_time: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Nav {
+    #[serde(rename = "isLogin")]
+    pub is_login: bool,
+    #[serde(default)]
+    pub mid: u64,
+    #[serde(default)]
+    pub uname: String,
+    pub wbi_img: Option<WbiImg>,
+}
+
+/// Urls of the images whose names are the WBI keys.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WbiImg {
+    pub img_url: String,
+    pub sub_url: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -65,91 +83,3 @@ where
         }
     }
 }
-
-pub fn bili_cookies<'a>(login_info: &'a LoginInfo, buvid: &'a str) -> HashMap<&'a str, &'a str> {
-    let cookies = match login_info
-        .cookie_info
-        .get("cookies")
-        .and_then(|c| c.as_array())
-    {
-        Some(c) => c,
-        None => return HashMap::new(),
-    };
-
-    let mut cookie_map: HashMap<&str, &str> = cookies
-        .iter()
-        .filter_map(|c| {
-            match (
-                c.get("name").and_then(|n| n.as_str()),
-                c.get("value").and_then(|v| v.as_str()),
-            ) {
-                (Some(name), Some(value)) => Some((name, value)),
-                _ => None,
-            }
-        })
-        .collect();
-
-    cookie_map.insert("buvid3", buvid);
-    cookie_map
-}
-
-pub fn bili_client(cookies: &HashMap<&str, &str>) -> reqwest::Result<reqwest::Client> {
-    let cookie_string = cookies
-        .iter()
-        .fold(String::new(), |acc, (k, v)| format!("{acc}{k}={v}; "));
-
-    let cookie_value = match HeaderValue::from_str(&cookie_string) {
-        Ok(c) => c,
-        Err(_) => HeaderValue::from_static(""),
-    };
-
-    let mut header_map = reqwest::header::HeaderMap::new();
-    header_map.insert(COOKIE, cookie_value);
-
-    reqwest::Client::builder()
-        .cookie_store(true)
-        .default_headers(header_map)
-        .user_agent(USER_AGENT)
-        .build()
-}
-
-type RoomInfo = (PlayInfo, DanmuInfo);
-
-pub async fn get_room_info(client: &reqwest::Client, room_id: u32) -> Result<RoomInfo, Error> {
-    client
-        .get(format!("https://live.bilibili.com/{room_id}"))
-        .send()
-        .await?;
-
-    let play_info = client
-        .get("https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo")
-        .query(&[
-            ("room_id", format!("{room_id}").as_str()),
-            ("protocol", "0"),
-            ("format", "0"),
-            ("codec", "0"),
-        ])
-        .send()
-        .await?
-        .json::<BiliResponse<PlayInfo>>()
-        .await?
-        .ok()?;
-
-    if play_info.room_id != room_id {
-        tracing::info!("[{room_id}] real room id: {}", play_info.room_id);
-    }
-
-    let danmu_info = client
-        .get("https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo")
-        .query(&[
-            ("id", format!("{}", play_info.room_id).as_str()),
-            ("type", "0"),
-        ])
-        .send()
-        .await?
-        .json::<BiliResponse<DanmuInfo>>()
-        .await?
-        .ok()?;
-
-    Ok((play_info, danmu_info))
-}
@@ -1,14 +1,25 @@
 mod buvid;
 mod handler;
 mod live;
-mod sub;
+mod proto;
+mod wbi;
 
+pub mod client;
+pub mod credential;
 pub mod danmu;
 pub mod info;
+pub mod notify;
+pub mod ntqq;
+pub mod sub;
+pub mod template;
 
-pub use buvid::gen_buvid3;
-pub use handler::{LiveMessage, LiveSubHandler};
-pub use live::connect_room;
+pub use buvid::{gen_buvid3, Buvid};
+pub use handler::{
+    ComboMessage, DanmuMessage, EntryEffect, FanMedal, GiftMessage, GuardBuyMessage, InteractKind,
+    InteractWord, LiveMessage, LiveSubHandler, OnlineRank, RankUser, SuperChatMessage,
+};
+pub use live::{connect_room, ConnectOptions, LiveTracker, Tracked, Transport};
+pub use sub::{Operation, Packet, PacketHeader, ProtoVer, SubCodec, SubDecoder, SubReply};
 
 use thiserror::Error;
 
@@ -23,12 +34,24 @@ pub enum Error {
     #[error("invalid sub: {0}")]
     DecodeSub(&'static str),
 
+    #[error("cannot encode sub: {0}")]
+    EncodeSub(&'static str),
+
     #[error("[{0}] failed to handle {1}")]
     Handler(u32, &'static str),
 
+    #[error(transparent)]
+    HeaderValue(#[from] tokio_tungstenite::tungstenite::http::header::InvalidHeaderValue),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    #[error(transparent)]
+    Mail(#[from] lettre::error::Error),
+
+    #[error(transparent)]
+    MailAddress(#[from] lettre::address::AddressError),
+
     #[error("missing data: {0}")]
     MissingData(&'static str),
 
@@ -41,6 +64,9 @@ pub enum Error {
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
 
+    #[error(transparent)]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
     #[error(transparent)]
     Tungstenite(#[from] tokio_tungstenite::tungstenite::Error),
 }
@@ -0,0 +1,38 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// Sliding-window limiter allowing at most `limit` actions per minute.
+pub struct RateLimiter {
+    limit: usize,
+    history: Mutex<VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn per_minute(limit: usize) -> Self {
+        Self {
+            limit,
+            history: Mutex::new(VecDeque::with_capacity(limit)),
+        }
+    }
+
+    pub async fn try_acquire(&self) -> bool {
+        let now = Instant::now();
+        let mut history = self.history.lock().await;
+
+        while let Some(t) = history.front() {
+            if now.duration_since(*t) < Duration::from_secs(60) {
+                break;
+            }
+            history.pop_front();
+        }
+
+        if history.len() >= self.limit {
+            return false;
+        }
+
+        history.push_back(now);
+        true
+    }
+}
@@ -1,41 +1,138 @@
-use std::collections::HashMap;
+use std::sync::Mutex;
 
-use futures_util::{SinkExt, StreamExt};
+use bytes::Bytes;
+use futures_util::{future, stream, Sink, SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use tokio::net::TcpStream;
 use tokio_tungstenite::connect_async;
+use tokio_util::codec::Framed;
+use tracing::{debug, info};
 
-use crate::info::{bili_client, get_room_info};
-use crate::sub::{auth_sub, heartbeat_sub};
+use crate::client::BiliClient;
+use crate::info::PlayInfo;
+use crate::sub::{auth_sub, heartbeat_sub, Packet, ProtoVer, SubCodec, SubDecoder, SubReply};
 use crate::Error;
-use crate::LiveSubHandler;
+use crate::{Buvid, LiveMessage, LiveSubHandler};
+
+/// How to connect to the danmu server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    #[default]
+    Wss,
+    Ws,
+    /// Raw packets over TCP, lighter than websockets.
+    Tcp,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConnectOptions {
+    #[serde(default)]
+    pub transport: Transport,
+    /// Encoding of messages: 0 or 1 for plain, 2 for zlib and 3 for brotli.
+    #[serde(default = "default_proto_ver")]
+    pub proto_ver: ProtoVer,
+    /// Authenticate as uid 0 instead of the logged in account. Usernames in
+    /// danmu are masked then.
+    #[serde(default)]
+    pub guest: bool,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            transport: Transport::default(),
+            proto_ver: default_proto_ver(),
+            guest: false,
+        }
+    }
+}
 
 pub async fn connect_room<H: LiveSubHandler + Sync>(
-    cookies: &HashMap<&str, &str>,
+    client: &BiliClient,
     room_id: u32,
+    options: &ConnectOptions,
     handler: H,
 ) -> Result<(), Error> {
-    let client = bili_client(cookies)?;
-    let (play_info, danmu_info) = get_room_info(&client, room_id).await?;
+    let play_info = client.room_info(room_id).await?;
+    let danmu_info = client.danmu_info(play_info.room_id).await?;
+    handler.handle_room_info(&play_info).await?;
 
-    let ws_url = danmu_info
+    let host = danmu_info
         .host_list
         .first()
-        .map(|h| format!("wss://{}:{}/sub", h.host, h.wss_port))
         .ok_or(Error::MissingData("danmu host"))?;
 
-    let (ws_stream, _) = connect_async(ws_url).await?;
-    let (mut write, mut read) = ws_stream.split();
+    let credentials = client.credentials();
+    let (uid, buvid) = if options.guest {
+        let buvid = credentials.buvid3().unwrap_or_else(|| {
+            let buvid = Buvid::generate();
+            credentials.set_buvid(&buvid);
+            buvid.buvid3
+        });
+        (0, buvid)
+    } else {
+        let uid = credentials.uid().ok_or(Error::MissingData("no uid"))?;
+        let buvid = credentials.buvid3().ok_or(Error::MissingData("no buvid"))?;
+        (uid, buvid)
+    };
+    let auth = auth_sub(
+        uid,
+        play_info.room_id,
+        &buvid,
+        &danmu_info.token,
+        options.proto_ver,
+    )?;
 
-    let writer = async {
-        let uid: u64 = cookies
-            .get("DedeUserID")
-            .ok_or(Error::MissingData("no uid"))?
-            .parse()?;
+    match options.transport {
+        Transport::Tcp => {
+            let stream = TcpStream::connect((host.host.as_str(), host.port)).await?;
+            let (write, read) = Framed::new(stream, SubCodec::new()).split();
+            run_session(write, read, auth, &handler).await
+        }
+        transport => {
+            let url = match transport {
+                Transport::Ws => format!("ws://{}:{}/sub", host.host, host.ws_port),
+                _ => format!("wss://{}:{}/sub", host.host, host.wss_port),
+            };
+
+            let (ws_stream, _) = connect_async(url).await?;
+            let (write, read) = ws_stream.split();
+            let write = write
+                .sink_map_err(Error::from)
+                .with(|packet: Packet| future::ready(Ok(packet.to_message())));
+            // Replies are decoded one at a time, reading the next message once
+            // those of the previous one are handled.
+            let read = stream::try_unfold(
+                (read, SubDecoder::new()),
+                |(mut read, mut decoder)| async move {
+                    loop {
+                        if let Some(reply) = decoder.next_reply()? {
+                            return Ok(Some((reply, (read, decoder))));
+                        }
+                        match read.next().await {
+                            Some(message) => decoder.feed(Bytes::from(message?.into_data())),
+                            None => return Ok(None),
+                        }
+                    }
+                },
+            );
+            run_session(write, read, auth, &handler).await
+        }
+    }
+}
 
-        let buvid = cookies
-            .get("buvid3")
-            .ok_or(Error::MissingData("no buvid"))?;
+/// Authenticates, then sends heartbeats while handling the replies.
+async fn run_session<W, R, H>(write: W, read: R, auth: Packet, handler: &H) -> Result<(), Error>
+where
+    W: Sink<Packet, Error = Error>,
+    R: Stream<Item = Result<SubReply, Error>>,
+    H: LiveSubHandler + Sync,
+{
+    let mut write = std::pin::pin!(write);
+    let mut read = std::pin::pin!(read);
 
-        let auth = auth_sub(uid, play_info.room_id, buvid, &danmu_info.token)?;
+    let writer = async {
         write.send(auth).await?;
 
         loop {
@@ -48,11 +145,8 @@ pub async fn connect_room<H: LiveSubHandler + Sync>(
     };
 
     let reader = async {
-        while let Some(message) = read.next().await {
-            let data = message?.into_data();
-            for reply in crate::sub::decode(data)? {
-                handler.handle_reply(reply).await?;
-            }
+        while let Some(reply) = read.next().await {
+            handler.handle_reply(reply?).await?;
         }
 
         Ok::<(), Error>(())
@@ -63,3 +157,159 @@ pub async fn connect_room<H: LiveSubHandler + Sync>(
         r = reader => r,
     }
 }
+
+/// Tracks the broadcast session of a room across reconnects, so that a
+/// handler sees the start of each session once.
+#[derive(Debug, Default)]
+pub struct LiveTracker {
+    /// `None` if offline, otherwise the `live_time` of the session if known.
+    session: Mutex<Option<Option<i64>>>,
+}
+
+impl LiveTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps a handler to receive the tracked messages.
+    pub fn track<H: LiveSubHandler + Sync>(&self, handler: H) -> Tracked<'_, H> {
+        Tracked {
+            tracker: self,
+            handler,
+        }
+    }
+
+    /// The `live_time` of the current session, if live and known.
+    pub fn live_time(&self) -> Option<i64> {
+        self.session.lock().unwrap().flatten()
+    }
+
+    /// Updates the session from the room info. Returns a `MissedLive` if the
+    /// room went live in a session not seen before.
+    pub fn update(&self, play_info: &PlayInfo) -> Option<LiveMessage> {
+        let mut session = self.session.lock().unwrap();
+        if play_info.live_status != 1 {
+            *session = None;
+            return None;
+        }
+
+        let live_time = play_info.live_time;
+        match *session {
+            Some(Some(t)) if t == live_time => None,
+            Some(None) => {
+                *session = Some(Some(live_time));
+                None
+            }
+            _ => {
+                *session = Some(Some(live_time));
+                Some(LiveMessage::MissedLive { live_time })
+            }
+        }
+    }
+
+    /// Returns whether the message should be handled, which is `false` for
+    /// repeated `LIVE` messages of a known session.
+    pub fn accept(&self, message: &LiveMessage) -> bool {
+        let mut session = self.session.lock().unwrap();
+        match message {
+            LiveMessage::Live { live_time } => match (*session, live_time) {
+                (Some(Some(t)), Some(live_time)) if t == *live_time => false,
+                (Some(_), None) => false,
+                (Some(None), Some(_)) => {
+                    *session = Some(*live_time);
+                    false
+                }
+                _ => {
+                    *session = Some(*live_time);
+                    true
+                }
+            },
+            LiveMessage::Preparing => {
+                *session = None;
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+pub struct Tracked<'a, H> {
+    tracker: &'a LiveTracker,
+    handler: H,
+}
+
+impl<'a, H: LiveSubHandler + Sync> LiveSubHandler for Tracked<'a, H> {
+    fn get_room_id(&self) -> u32 {
+        self.handler.get_room_id()
+    }
+
+    async fn handle_message(&self, message: &LiveMessage) -> Result<(), Error> {
+        if !self.tracker.accept(message) {
+            debug!("[{}] skip repeated {message:?}", self.get_room_id());
+            return Ok(());
+        }
+
+        self.handler.handle_message(message).await
+    }
+
+    async fn handle_room_info(&self, play_info: &PlayInfo) -> Result<(), Error> {
+        self.handler.handle_room_info(play_info).await?;
+
+        if let Some(message) = self.tracker.update(play_info) {
+            info!("[{}] went live while disconnected", self.get_room_id());
+            self.handler.handle_message(&message).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn default_proto_ver() -> ProtoVer {
+    ProtoVer::Brotli
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_info(live_time: i64) -> PlayInfo {
+        PlayInfo {
+            room_id: 1,
+            short_id: 0,
+            live_status: 1,
+            live_time,
+        }
+    }
+
+    #[test]
+    fn learns_live_time_of_bare_live() {
+        let tracker = LiveTracker::new();
+        assert!(tracker.accept(&LiveMessage::Live { live_time: None }));
+        assert_eq!(tracker.live_time(), None);
+
+        // Not handled again, but the session is known from then on.
+        assert!(!tracker.accept(&LiveMessage::Live {
+            live_time: Some(1700000000)
+        }));
+        assert_eq!(tracker.live_time(), Some(1700000000));
+        assert_eq!(tracker.update(&play_info(1700000000)), None);
+
+        assert!(tracker.accept(&LiveMessage::Preparing));
+        assert_eq!(tracker.live_time(), None);
+    }
+
+    #[test]
+    fn reports_missed_session() {
+        let tracker = LiveTracker::new();
+        assert_eq!(
+            tracker.update(&play_info(1700000000)),
+            Some(LiveMessage::MissedLive {
+                live_time: 1700000000
+            })
+        );
+        assert!(!tracker.accept(&LiveMessage::Live {
+            live_time: Some(1700000000)
+        }));
+        assert_eq!(tracker.update(&play_info(1700000000)), None);
+    }
+}
@@ -1,10 +1,23 @@
+mod accounts;
+mod auto_reply;
+mod bridge;
+mod config;
 mod greeting_bot;
+mod limiter;
+mod notifier;
+mod relay;
+mod room_bot;
+mod rooms;
+mod scheduler;
+mod sender;
+mod store;
+mod thanks;
+mod welcome;
 
-use biliup::credential::login_by_cookies;
-use blive_greeting::info::bili_cookies;
-use blive_greeting::{connect_room, gen_buvid3};
-use greeting_bot::LiveGreetingBot;
-use tracing::error;
+use accounts::Accounts;
+use config::Config;
+use rooms::RoomManager;
+use store::StateStore;
 
 #[tokio::main]
 async fn main() {
@@ -14,22 +27,36 @@ async fn main() {
 
     tracing_subscriber::fmt::init();
 
-    let bili = login_by_cookies("cookies.json")
+    let config = Config::load("config.json")
         .await
-        .expect("failed to login");
+        .expect("failed to load config.json");
 
-    tokio::join!(run(&bili, 4588774), run(&bili, 21669627), run(&bili, 33989));
-}
+    assert!(
+        config.connect.guest || !config.accounts.is_empty(),
+        "no account configured"
+    );
+    let accounts = Accounts::login(&config.accounts, &config.http, config.fetch_buvid).await;
+    let guest = if config.connect.guest {
+        Some(accounts::guest(&config.http, config.fetch_buvid).await)
+    } else {
+        None
+    };
 
-async fn run(bili: &biliup::bilibili::BiliBili, room_id: u32) {
-    loop {
-        let buvid = gen_buvid3();
-        let cookies = bili_cookies(&bili.login_info, &buvid);
-        let handler = LiveGreetingBot::new(room_id, &cookies);
-        match connect_room(&cookies, room_id, handler).await {
-            Ok(_) => (),
-            Err(e) => error!("failed to connect room {room_id}: {e}"),
-        }
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    let store = StateStore::load(&config.state_file)
+        .await
+        .expect("failed to load state");
+
+    let rooms = RoomManager::new(accounts, guest, config.clone(), store);
+    for room_id in &config.rooms {
+        rooms.watch(*room_id).await;
     }
+
+    let bridge = async {
+        match &config.onebot {
+            Some(onebot) => bridge::run(onebot, &rooms).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::join!(rooms.check_accounts(), bridge);
 }
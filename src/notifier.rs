@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use blive_greeting::notify::{Event, Notifier};
+use blive_greeting::ntqq::OneBot;
+use blive_greeting::{Error, Result};
+use tracing::{debug, error};
+
+use crate::config::{NotifierConfig, Sink};
+
+/// Routes events to the sinks configured for their room and kind.
+pub struct Notifiers {
+    onebot: Option<Arc<OneBot>>,
+    configs: Vec<NotifierConfig>,
+}
+
+impl Notifiers {
+    pub fn new(onebot: Option<Arc<OneBot>>, configs: Vec<NotifierConfig>) -> Self {
+        Self { onebot, configs }
+    }
+
+    pub async fn notify(&self, event: &Event) {
+        let configs = self.configs.iter().filter(|c| {
+            // Events about no room pass any filter of rooms.
+            let room = match event.room_id {
+                Some(room_id) => c.rooms.is_empty() || c.rooms.contains(&room_id),
+                None => true,
+            };
+            room && (c.events.is_empty() || c.events.contains(&event.kind))
+        });
+
+        let room = event.room_id.map_or(String::new(), |id| id.to_string());
+        for config in configs {
+            match self.send(&config.sink, event).await {
+                Ok(_) => debug!("[{room}] notified {}", event.kind.as_str()),
+                Err(e) => error!("[{room}] notify error: {e}"),
+            }
+        }
+    }
+
+    async fn send(&self, sink: &Sink, event: &Event) -> Result<()> {
+        match sink {
+            Sink::Onebot { peer } => {
+                let onebot = self.onebot.as_ref().ok_or(Error::MissingData("onebot"))?;
+                onebot.send_message(peer, &event.message).await
+            }
+            Sink::Webhook(n) => n.notify(event).await,
+            Sink::Telegram(n) => n.notify(event).await,
+            Sink::Discord(n) => n.notify(event).await,
+            Sink::Smtp(n) => n.notify(event).await,
+        }
+    }
+}
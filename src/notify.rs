@@ -0,0 +1,337 @@
+use std::future::Future;
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::template::render;
+use crate::{LiveMessage, Result};
+
+pub trait Notifier {
+    fn notify(&self, event: &Event) -> impl Future<Output = Result<()>> + Send;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Live,
+    Preparing,
+    SuperChat,
+    Alert,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Live => "live",
+            EventKind::Preparing => "preparing",
+            EventKind::SuperChat => "super_chat",
+            EventKind::Alert => "alert",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub kind: EventKind,
+    /// `None` for events about no room, such as alerts about accounts.
+    pub room_id: Option<u32>,
+    pub message: String,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, room_id: u32, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            room_id: Some(room_id),
+            message: message.into(),
+        }
+    }
+
+    /// An alert about no room, sent whatever the rooms of a notifier.
+    pub fn alert(message: impl Into<String>) -> Self {
+        Self {
+            kind: EventKind::Alert,
+            room_id: None,
+            message: message.into(),
+        }
+    }
+
+    /// Builds the event to notify about a live message, if any.
+    pub fn from_message(room_id: u32, message: &LiveMessage) -> Option<Self> {
+        match message {
+            LiveMessage::Live { .. } | LiveMessage::MissedLive { .. } => Some(Self::new(
+                EventKind::Live,
+                room_id,
+                format!("room {room_id} is live"),
+            )),
+            LiveMessage::Preparing => Some(Self::new(
+                EventKind::Preparing,
+                room_id,
+                format!("room {room_id} is offline"),
+            )),
+            LiveMessage::SuperChat(sc) => Some(Self::new(
+                EventKind::SuperChat,
+                room_id,
+                format!("[SC ¥{}] {}: {}", sc.price, sc.uname, sc.message),
+            )),
+            _ => None,
+        }
+    }
+
+    fn subject(&self) -> String {
+        match self.room_id {
+            Some(room_id) => format!("[{room_id}] {}", self.kind.as_str()),
+            None => self.kind.as_str().to_string(),
+        }
+    }
+}
+
+/// Posts the event as JSON to an arbitrary URL.
+///
+/// `body` is a template with `{kind}`, `{room_id}` and `{message}` placeholders,
+/// which are JSON-escaped. `{room_id}` is `null` for events about no room. The
+/// serialized event is posted if it is not set.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    pub body: Option<String>,
+}
+
+impl Notifier for Webhook {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        let body = match &self.body {
+            Some(template) => {
+                let escape = |s: &str| {
+                    let quoted = serde_json::to_string(s).unwrap_or_default();
+                    quoted[1..quoted.len() - 1].to_string()
+                };
+                render(
+                    template,
+                    &[
+                        ("kind", event.kind.as_str().to_string()),
+                        ("room_id", json!(event.room_id).to_string()),
+                        ("message", escape(&event.message)),
+                    ],
+                )
+            }
+            None => serde_json::to_string(event)?,
+        };
+
+        Client::new()
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Telegram {
+    #[serde(default = "default_telegram_api")]
+    pub api: String,
+    pub token: String,
+    pub chat_id: i64,
+}
+
+impl Notifier for Telegram {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        Client::new()
+            .post(format!("{}/bot{}/sendMessage", self.api, self.token))
+            .json(&json!({
+                "chat_id": self.chat_id,
+                "text": event.message,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Discord {
+    pub url: String,
+}
+
+impl Notifier for Discord {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        Client::new()
+            .post(&self.url)
+            .json(&json!({ "content": event.message }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Smtp {
+    pub host: String,
+    pub port: Option<u16>,
+    /// Upgrade the connection with STARTTLS.
+    #[serde(default = "default_smtp_tls")]
+    pub tls: bool,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl Notifier for Smtp {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        let mut builder = match self.tls {
+            true => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?,
+            false => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+        };
+
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let mut message = lettre::Message::builder()
+            .from(self.from.parse::<Mailbox>()?)
+            .subject(event.subject());
+        for to in &self.to {
+            message = message.to(to.parse::<Mailbox>()?);
+        }
+        let message = message.body(event.message.clone())?;
+
+        builder.build().send(message).await?;
+
+        Ok(())
+    }
+}
+
+fn default_telegram_api() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+fn default_smtp_tls() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Accepts one request and answers 200. Returns the url to post to and
+    /// the request body.
+    async fn stand_in() -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            let body = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+
+                let text = String::from_utf8_lossy(&request).to_string();
+                let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+                let length = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(str::to_string)
+                    })
+                    .and_then(|l| l.parse::<usize>().ok())
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break body.to_string();
+                }
+            };
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}")
+                .await
+                .unwrap();
+            body
+        });
+
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn webhook_renders_json_template() {
+        let (url, request) = stand_in().await;
+        let webhook = Webhook {
+            url,
+            body: Some(r#"{"text": "{message}", "room": {room_id}}"#.to_string()),
+        };
+
+        let event = Event::new(EventKind::Live, 1, r#"say "hi""#);
+        webhook.notify(&event).await.unwrap();
+
+        assert_eq!(
+            request.await.unwrap(),
+            r#"{"text": "say \"hi\"", "room": 1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_event() {
+        let (url, request) = stand_in().await;
+        let webhook = Webhook { url, body: None };
+
+        let event = Event::new(EventKind::Alert, 1, "logged out");
+        webhook.notify(&event).await.unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&request.await.unwrap()).unwrap();
+        assert_eq!(body["kind"], "alert");
+        assert_eq!(body["message"], "logged out");
+    }
+
+    #[tokio::test]
+    async fn telegram_sends_message() {
+        let (api, request) = stand_in().await;
+        let telegram = Telegram {
+            api,
+            token: "token".to_string(),
+            chat_id: 42,
+        };
+
+        let event = Event::new(EventKind::Live, 1, "room 1 is live");
+        telegram.notify(&event).await.unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&request.await.unwrap()).unwrap();
+        assert_eq!(body["chat_id"], 42);
+        assert_eq!(body["text"], "room 1 is live");
+    }
+
+    #[tokio::test]
+    async fn webhook_renders_alert_without_room() {
+        let (url, request) = stand_in().await;
+        let webhook = Webhook {
+            url,
+            body: Some(r#"{"room": {room_id}}"#.to_string()),
+        };
+
+        webhook.notify(&Event::alert("logged out")).await.unwrap();
+
+        assert_eq!(request.await.unwrap(), r#"{"room": null}"#);
+    }
+}
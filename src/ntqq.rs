@@ -1,19 +1,59 @@
-use reqwest::{Client, Error};
+use futures_util::{Stream, StreamExt};
+use reqwest::Client;
+use serde::Deserialize;
 use serde_json::json;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header::AUTHORIZATION, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
 
-pub struct OneBot<'a> {
-    endpoint: &'a str,
-    token: &'a str,
+use crate::Error;
+
+pub struct OneBot {
+    endpoint: String,
+    token: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Peer {
     Friend(i64),
     Group(i64),
 }
 
-impl<'a> OneBot<'a> {
-    pub fn new(endpoint: &'a str, token: &'a str) -> Self {
-        Self { endpoint, token }
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "post_type")]
+pub enum OneBotEvent {
+    #[serde(rename = "message")]
+    Message(MessageEvent),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MessageEvent {
+    pub message_type: String,
+    pub user_id: i64,
+    pub group_id: Option<i64>,
+    pub raw_message: String,
+}
+
+impl MessageEvent {
+    /// The peer a reply to this message should be sent to.
+    pub fn peer(&self) -> Peer {
+        match (self.message_type.as_str(), self.group_id) {
+            ("group", Some(group_id)) => Peer::Group(group_id),
+            _ => Peer::Friend(self.user_id),
+        }
+    }
+}
+
+impl OneBot {
+    pub fn new(endpoint: &str, token: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            token: token.to_string(),
+        }
     }
 
     pub async fn send_message(&self, peer: &Peer, message: &str) -> Result<(), Error> {
@@ -24,7 +64,7 @@ impl<'a> OneBot<'a> {
 
         Client::new()
             .post(format!("{}/send_msg", self.endpoint))
-            .bearer_auth(self.token)
+            .bearer_auth(&self.token)
             .json(&json!({
                 id_kind: id,
                 "message": message
@@ -34,4 +74,26 @@ impl<'a> OneBot<'a> {
 
         Ok(())
     }
+
+    /// Connects to the forward WebSocket event endpoint of the OneBot server.
+    pub async fn events(
+        &self,
+        event_url: &str,
+    ) -> Result<impl Stream<Item = Result<OneBotEvent, Error>>, Error> {
+        let mut request = event_url.into_client_request()?;
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.token))?,
+        );
+
+        let (ws_stream, _) = connect_async(request).await?;
+
+        Ok(ws_stream.filter_map(|message| async {
+            match message {
+                Ok(Message::Text(text)) => Some(serde_json::from_str(&text).map_err(Error::from)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e.into())),
+            }
+        }))
+    }
 }
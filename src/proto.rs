@@ -0,0 +1,318 @@
+//! Protobuf payloads embedded as base64 in some messages. Only the fields
+//! used are declared; the rest are skipped when decoding.
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use prost::Message;
+
+use crate::handler::{DanmuMessage, FanMedal, InteractWord, OnlineRank, RankUser};
+
+/// `dm_v2` of `DANMU_MSG`.
+#[derive(Clone, PartialEq, Message)]
+struct Dm {
+    #[prost(string, tag = "6")]
+    content: String,
+    /// Milliseconds.
+    #[prost(int64, tag = "7")]
+    ctime: i64,
+    #[prost(message, optional, tag = "20")]
+    user: Option<User>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct User {
+    #[prost(int64, tag = "1")]
+    uid: i64,
+    #[prost(message, optional, tag = "2")]
+    base: Option<UserBase>,
+    #[prost(message, optional, tag = "3")]
+    medal: Option<UserMedal>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct UserBase {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct UserMedal {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(int64, tag = "2")]
+    level: i64,
+    /// Uid of the streamer.
+    #[prost(int64, tag = "10")]
+    ruid: i64,
+}
+
+/// `pb` of `INTERACT_WORD_V2`.
+#[derive(Clone, PartialEq, Message)]
+struct Interact {
+    #[prost(int64, tag = "1")]
+    uid: i64,
+    #[prost(string, tag = "2")]
+    uname: String,
+    #[prost(int64, tag = "5")]
+    msg_type: i64,
+    #[prost(int64, tag = "7")]
+    timestamp: i64,
+    #[prost(message, optional, tag = "9")]
+    fans_medal: Option<FansMedalInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FansMedalInfo {
+    #[prost(int64, tag = "1")]
+    target_id: i64,
+    #[prost(int64, tag = "2")]
+    medal_level: i64,
+    #[prost(string, tag = "3")]
+    medal_name: String,
+    #[prost(int64, tag = "12")]
+    anchor_roomid: i64,
+}
+
+/// `pb` of `ONLINE_RANK_V3`.
+#[derive(Clone, PartialEq, Message)]
+struct GoldRank {
+    #[prost(string, tag = "1")]
+    rank_type: String,
+    #[prost(message, repeated, tag = "2")]
+    list: Vec<GoldRankItem>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GoldRankItem {
+    #[prost(int64, tag = "1")]
+    uid: i64,
+    #[prost(string, tag = "3")]
+    score: String,
+    #[prost(string, tag = "4")]
+    uname: String,
+    #[prost(int64, tag = "5")]
+    rank: i64,
+    #[prost(int64, tag = "6")]
+    guard_level: i64,
+}
+
+fn decode<M: Message + Default>(pb: &str) -> Option<M> {
+    let data = BASE64_STANDARD.decode(pb).ok()?;
+    M::decode(data.as_slice()).ok()
+}
+
+pub(crate) fn danmu(dm_v2: &str) -> Option<DanmuMessage> {
+    let dm: Dm = decode(dm_v2)?;
+    let user = dm.user?;
+    let medal = user
+        .medal
+        .filter(|medal| medal.level > 0)
+        .map(|medal| FanMedal {
+            level: medal.level as u64,
+            name: medal.name,
+            target_name: None,
+            room_id: None,
+            target_id: medal.ruid as u64,
+        });
+
+    Some(DanmuMessage {
+        content: dm.content,
+        uid: user.uid as u64,
+        uname: user.base?.name,
+        medal,
+        ts: dm.ctime as u64 / 1000,
+    })
+}
+
+pub(crate) fn interact_word(pb: &str) -> Option<InteractWord> {
+    let interact: Interact = decode(pb)?;
+    let medal = interact
+        .fans_medal
+        .filter(|medal| medal.medal_level > 0)
+        .map(|medal| FanMedal {
+            level: medal.medal_level as u64,
+            name: medal.medal_name,
+            target_name: None,
+            room_id: (medal.anchor_roomid > 0).then_some(medal.anchor_roomid as u64),
+            target_id: medal.target_id as u64,
+        });
+
+    Some(InteractWord {
+        uid: interact.uid as u64,
+        uname: interact.uname,
+        kind: (interact.msg_type as u64).into(),
+        medal,
+        ts: interact.timestamp as u64,
+    })
+}
+
+pub(crate) fn online_rank(pb: &str) -> Option<OnlineRank> {
+    let rank: GoldRank = decode(pb)?;
+    let list = rank
+        .list
+        .into_iter()
+        .map(|item| RankUser {
+            uid: item.uid as u64,
+            uname: item.uname,
+            rank: item.rank as u64,
+            score: item.score,
+            guard_level: item.guard_level as u64,
+        })
+        .collect();
+
+    Some(OnlineRank {
+        rank_type: rank.rank_type,
+        list,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::handler::{InteractKind, LiveMessage};
+
+    // Encoded with the fields Bilibili sends, not only those declared.
+    const DM_V2: &str = "CgplMmExYjNjNGQ1EAEYGSD///8HKghiMWMyZDNlNDIP5Li75pKt5pma5LiK5aW9OPu4noaAMkACogFoCM7C8QUSPAoJ6KeC5LyX55SyEi9odHRwczovL2kwLmhkc2xiLmNvbS9iZnMvZmFjZS9tZW1iZXIvbm9mYWNlLmpwZxojCgnnsonkuJ3lm6IQFRjMqhgg8ZGiAzjAxAdIAVCx/+UpWAM=";
+    const INTERACT_WORD_V2: &str = "CM7C8QUSCeinguS8l+eUsiADIAEoATDN1eUKOICzmrMGQPu4noaAMkodCLH/5SkQFRoJ57KJ5Lid5ZuiIMyqGDgDYM3V5QpQAA==";
+    const ONLINE_RANK_V3: &str = "Cglnb2xkLXJhbmsSSwjOwvEFEi9odHRwczovL2kwLmhkc2xiLmNvbS9iZnMvZmFjZS9tZW1iZXIvbm9mYWNlLmpwZxoEMTIwMCIJ6KeC5LyX55SyKAEwAxJKCJXYlwsSL2h0dHBzOi8vaTAuaGRzbGIuY29tL2Jmcy9mYWNlL21lbWJlci9ub2ZhY2UuanBnGgM1MjAiCeinguS8l+S5mSgCMAA=";
+
+    fn medal(target_name: Option<&str>, room_id: Option<u64>) -> Option<FanMedal> {
+        Some(FanMedal {
+            level: 21,
+            name: "粉丝团".to_string(),
+            target_name: target_name.map(str::to_string),
+            room_id,
+            target_id: 87654321,
+        })
+    }
+
+    fn parse(message: serde_json::Value) -> LiveMessage {
+        serde_json::from_value(message).unwrap()
+    }
+
+    #[test]
+    fn decodes_danmu() {
+        let danmu = danmu(DM_V2).unwrap();
+        assert_eq!(
+            danmu,
+            DanmuMessage {
+                content: "主播晚上好".to_string(),
+                uid: 12345678,
+                uname: "观众甲".to_string(),
+                medal: medal(None, None),
+                ts: 1718000000,
+            }
+        );
+
+        let message = parse(json!({
+            "cmd": "DANMU_MSG",
+            "dm_v2": DM_V2,
+            "info": [
+                [0, 1, 25, 16777215, 1718000000123_u64, 1718000000, 0, "b1c2d3e4", 0, 0, 0, "", 0, "{}", "{}", {}],
+                "主播晚上好",
+                [12345678, "观众甲", 0, 0, 0, 10000, 1, ""],
+                [21, "粉丝团", "主播", 22637261, 398668, "", 0, 6809855, 398668, 6850801, 3, 1, 87654321],
+                [0, 0, 9868950, ">50000", 0],
+                ["", ""],
+                0,
+                3,
+                null,
+                {"ts": 1718000000, "ct": "E2A1B3C4"},
+                0,
+                0,
+                null,
+                null,
+                0,
+                105
+            ]
+        }));
+        // The same but for what only the JSON form carries.
+        let danmu = DanmuMessage {
+            medal: medal(Some("主播"), Some(22637261)),
+            ..danmu
+        };
+        assert_eq!(message, LiveMessage::Danmu(danmu));
+    }
+
+    #[test]
+    fn decodes_interact_word() {
+        let interact = interact_word(INTERACT_WORD_V2).unwrap();
+        assert_eq!(
+            interact,
+            InteractWord {
+                uid: 12345678,
+                uname: "观众甲".to_string(),
+                kind: InteractKind::Enter,
+                medal: medal(None, Some(22637261)),
+                ts: 1718000000,
+            }
+        );
+
+        let message = parse(json!({
+            "cmd": "INTERACT_WORD",
+            "data": {
+                "uid": 12345678,
+                "uname": "观众甲",
+                "identities": [3, 1],
+                "msg_type": 1,
+                "roomid": 22637261,
+                "timestamp": 1718000000,
+                "score": 1718000000123_u64,
+                "fans_medal": {
+                    "target_id": 87654321,
+                    "medal_level": 21,
+                    "medal_name": "粉丝团",
+                    "medal_color": 398668,
+                    "guard_level": 3,
+                    "anchor_roomid": 22637261
+                }
+            }
+        }));
+        assert_eq!(message, LiveMessage::Interact(interact));
+    }
+
+    #[test]
+    fn decodes_online_rank() {
+        let rank = online_rank(ONLINE_RANK_V3).unwrap();
+        assert_eq!(rank.rank_type, "gold-rank");
+        assert_eq!(
+            rank.list,
+            [
+                RankUser {
+                    uid: 12345678,
+                    uname: "观众甲".to_string(),
+                    rank: 1,
+                    score: "1200".to_string(),
+                    guard_level: 3,
+                },
+                RankUser {
+                    uid: 23456789,
+                    uname: "观众乙".to_string(),
+                    rank: 2,
+                    score: "520".to_string(),
+                    guard_level: 0,
+                },
+            ]
+        );
+
+        let face = "https://i0.hdslb.com/bfs/face/member/noface.jpg";
+        let message = parse(json!({
+            "cmd": "ONLINE_RANK_V2",
+            "data": {
+                "rank_type": "gold-rank",
+                "list": [
+                    {"uid": 12345678, "face": face, "score": "1200", "uname": "观众甲", "rank": 1, "guard_level": 3},
+                    {"uid": 23456789, "face": face, "score": "520", "uname": "观众乙", "rank": 2, "guard_level": 0}
+                ]
+            }
+        }));
+        assert_eq!(message, LiveMessage::OnlineRank(rank));
+    }
+
+    #[test]
+    fn rejects_invalid_payloads() {
+        assert_eq!(danmu("not base64"), None);
+        assert_eq!(interact_word("AAAA"), None);
+    }
+}
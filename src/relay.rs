@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use blive_greeting::ntqq::{OneBot, Peer};
+use blive_greeting::{DanmuMessage, LiveMessage, LiveSubHandler, Result};
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+use crate::config::RelayConfig;
+use crate::limiter::RateLimiter;
+
+pub struct DanmuRelay {
+    config: RelayConfig,
+    onebot: Arc<OneBot>,
+    limiter: RateLimiter,
+    batch: Mutex<Batch>,
+}
+
+#[derive(Default)]
+struct Batch {
+    super_chats: Vec<String>,
+    danmu: Vec<String>,
+    omitted: usize,
+}
+
+impl Batch {
+    /// Takes the lines to send. Super chats past `max_batch` wait for the
+    /// next batch, as all of them are forwarded.
+    fn take(&mut self, max_batch: usize) -> Self {
+        let count = self.super_chats.len().min(max_batch.max(1));
+        Self {
+            super_chats: self.super_chats.drain(..count).collect(),
+            danmu: std::mem::take(&mut self.danmu),
+            omitted: std::mem::take(&mut self.omitted),
+        }
+    }
+}
+
+impl DanmuRelay {
+    pub fn new(config: RelayConfig, onebot: Arc<OneBot>) -> Self {
+        Self {
+            limiter: RateLimiter::per_minute(config.max_per_minute),
+            config,
+            onebot,
+            batch: Mutex::new(Batch::default()),
+        }
+    }
+
+    /// Sends the collected messages to the group every `interval` seconds.
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval));
+        loop {
+            interval.tick().await;
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&self) {
+        let batch = {
+            let mut batch = self.batch.lock().await;
+            if batch.super_chats.is_empty() && batch.danmu.is_empty() {
+                return;
+            }
+
+            if !self.limiter.try_acquire().await {
+                debug!("[{}] relay rate limited", self.config.room_id);
+                return;
+            }
+
+            batch.take(self.config.max_batch)
+        };
+
+        let mut lines = vec![format!("[{}]", self.config.room_id)];
+        lines.extend(batch.super_chats);
+        lines.extend(batch.danmu);
+        if batch.omitted > 0 {
+            lines.push(format!("({} more omitted)", batch.omitted));
+        }
+
+        let peer = Peer::Group(self.config.group_id);
+        if let Err(e) = self.onebot.send_message(&peer, &lines.join("\n")).await {
+            error!("[{}] relay error: {e}", self.config.room_id);
+        }
+    }
+
+    fn matches(&self, danmu: &DanmuMessage) -> bool {
+        let medal = match (self.config.medal_level, &danmu.medal) {
+            (Some(level), Some(medal)) => medal.level >= level,
+            _ => false,
+        };
+
+        medal
+            || self.config.uids.contains(&danmu.uid)
+            || self
+                .config
+                .keywords
+                .iter()
+                .any(|k| danmu.content.contains(k))
+    }
+}
+
+impl LiveSubHandler for DanmuRelay {
+    fn get_room_id(&self) -> u32 {
+        self.config.room_id
+    }
+
+    async fn handle_message(&self, message: &LiveMessage) -> Result<()> {
+        match message {
+            LiveMessage::Danmu(danmu) if self.matches(danmu) => {
+                let mut batch = self.batch.lock().await;
+                if batch.danmu.len() < self.config.max_batch {
+                    batch
+                        .danmu
+                        .push(format!("{}: {}", danmu.uname, danmu.content));
+                } else {
+                    batch.omitted += 1;
+                }
+            }
+            LiveMessage::SuperChat(sc) => {
+                let line = format!("[SC ¥{}] {}: {}", sc.price, sc.uname, sc.message);
+                self.batch.lock().await.super_chats.push(line);
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_super_chats_over() {
+        let mut batch = Batch {
+            super_chats: (0..5).map(|i| format!("sc {i}")).collect(),
+            danmu: vec!["danmu".to_string()],
+            omitted: 3,
+        };
+
+        let sent = batch.take(2);
+        assert_eq!(sent.super_chats, ["sc 0", "sc 1"]);
+        assert_eq!((sent.danmu.len(), sent.omitted), (1, 3));
+        assert_eq!(batch.super_chats, ["sc 2", "sc 3", "sc 4"]);
+        assert_eq!((batch.danmu.len(), batch.omitted), (0, 0));
+
+        assert_eq!(batch.take(0).super_chats, ["sc 2"]);
+    }
+}
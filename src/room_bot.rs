@@ -0,0 +1,140 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+
+use blive_greeting::client::BiliClient;
+use blive_greeting::info::PlayInfo;
+use blive_greeting::notify::Event;
+use blive_greeting::{ConnectOptions, LiveMessage, LiveSubHandler, LiveTracker, Result};
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::accounts::Account;
+use crate::auto_reply::AutoReplier;
+use crate::config::GreetingConfig;
+use crate::greeting_bot::LiveGreetingBot;
+use crate::limiter::RateLimiter;
+use crate::notifier::Notifiers;
+use crate::relay::DanmuRelay;
+use crate::scheduler::Scheduler;
+use crate::sender::DanmuSender;
+use crate::store::StateStore;
+use crate::thanks::GiftThanksHandler;
+use crate::welcome::ViewerGreeter;
+
+/// Features of a room which keep their state across reconnects.
+pub struct Features {
+    pub tracker: LiveTracker,
+    /// Client connecting to the room.
+    pub client: BiliClient,
+    pub connect: ConnectOptions,
+    /// Accounts for the room in order of preference.
+    pub accounts: Vec<Arc<Account>>,
+    /// Index of the account currently sending danmu.
+    pub account: AtomicUsize,
+    /// Features sending danmu are `None` without an account to send with.
+    pub greeting: Option<GreetingConfig>,
+    /// `live_time` of the session waiting to be greeted.
+    pub pending: watch::Sender<Option<Option<i64>>>,
+    pub notifiers: Arc<Notifiers>,
+    pub limiter: Arc<RateLimiter>,
+    pub store: Arc<StateStore>,
+    pub relay: Option<DanmuRelay>,
+    pub welcome: Option<ViewerGreeter>,
+    pub thanks: Option<GiftThanksHandler>,
+    pub auto_reply: Option<AutoReplier>,
+    pub scheduler: Option<Scheduler>,
+}
+
+/// Dispatches the messages of a room to every enabled feature.
+pub struct RoomBot<'a> {
+    pub room_id: u32,
+    pub greeting: Option<LiveGreetingBot<'a>>,
+    pub sender: DanmuSender<'a>,
+    pub features: &'a Features,
+}
+
+impl<'a> RoomBot<'a> {
+    /// Runs the timed tasks of the features while connected.
+    pub async fn run(&self) {
+        let greeting = async {
+            match &self.greeting {
+                Some(greeting) => greeting.run(&self.sender).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let relay = async {
+            match &self.features.relay {
+                Some(relay) => relay.run().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let thanks = async {
+            match &self.features.thanks {
+                Some(thanks) => thanks.run(&self.sender).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let scheduler = async {
+            match &self.features.scheduler {
+                Some(scheduler) => scheduler.run(&self.sender).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::join!(greeting, relay, thanks, scheduler);
+    }
+}
+
+impl<'a> LiveSubHandler for RoomBot<'a> {
+    fn get_room_id(&self) -> u32 {
+        self.room_id
+    }
+
+    async fn handle_message(&self, message: &LiveMessage) -> Result<()> {
+        if let LiveMessage::LogInNotice { message } = message {
+            warn!("[{}] usernames are masked: {message}", self.get_room_id());
+        }
+
+        if let Some(event) = Event::from_message(self.get_room_id(), message) {
+            let notifiers = self.features.notifiers.clone();
+            tokio::spawn(async move { notifiers.notify(&event).await });
+        }
+
+        if let Some(greeting) = &self.greeting {
+            greeting.handle_message(message).await?;
+        }
+
+        if let Some(relay) = &self.features.relay {
+            relay.handle_message(message).await?;
+        }
+
+        if let Some(welcome) = &self.features.welcome {
+            welcome.handle_message(message, &self.sender).await;
+        }
+
+        if let Some(thanks) = &self.features.thanks {
+            thanks.handle_message(message, &self.sender).await;
+        }
+
+        if let Some(auto_reply) = &self.features.auto_reply {
+            auto_reply.handle_message(message, &self.sender).await;
+        }
+
+        if let Some(scheduler) = &self.features.scheduler {
+            scheduler.handle_message(message);
+        }
+
+        Ok(())
+    }
+
+    async fn handle_room_info(&self, play_info: &PlayInfo) -> Result<()> {
+        if let Some(scheduler) = &self.features.scheduler {
+            scheduler.set_live(play_info.live_status == 1);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use blive_greeting::client::BiliClient;
+use blive_greeting::danmu::send_greeting;
+use blive_greeting::notify::Event;
+use blive_greeting::ntqq::OneBot;
+use blive_greeting::{connect_room, Error, LiveTracker, Result};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::accounts::Accounts;
+use crate::auto_reply::AutoReplier;
+use crate::config::Config;
+use crate::greeting_bot::LiveGreetingBot;
+use crate::limiter::RateLimiter;
+use crate::notifier::Notifiers;
+use crate::relay::DanmuRelay;
+use crate::room_bot::{Features, RoomBot};
+use crate::scheduler::Scheduler;
+use crate::sender::DanmuSender;
+use crate::store::StateStore;
+use crate::thanks::GiftThanksHandler;
+use crate::welcome::ViewerGreeter;
+
+pub struct RoomManager {
+    accounts: Accounts,
+    /// Client connecting to every room, if connecting as guest.
+    guest: Option<BiliClient>,
+    config: Arc<Config>,
+    onebot: Option<Arc<OneBot>>,
+    notifiers: Arc<Notifiers>,
+    limiter: Arc<RateLimiter>,
+    store: Arc<StateStore>,
+    rooms: Mutex<HashMap<u32, JoinHandle<()>>>,
+}
+
+impl RoomManager {
+    pub fn new(
+        accounts: Accounts,
+        guest: Option<BiliClient>,
+        config: Config,
+        store: StateStore,
+    ) -> Self {
+        let onebot = config
+            .onebot
+            .as_ref()
+            .map(|c| Arc::new(OneBot::new(&c.endpoint, &c.token)));
+
+        let notifiers = Notifiers::new(onebot.clone(), config.notifiers.clone());
+        let limiter = RateLimiter::per_minute(config.send_per_minute);
+
+        Self {
+            accounts,
+            guest,
+            config: Arc::new(config),
+            onebot,
+            notifiers: Arc::new(notifiers),
+            limiter: Arc::new(limiter),
+            store: Arc::new(store),
+            rooms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts watching a room. Returns `false` if the room is already watched.
+    pub async fn watch(&self, room_id: u32) -> bool {
+        let mut rooms = self.rooms.lock().await;
+        if rooms.contains_key(&room_id) {
+            return false;
+        }
+
+        let handle = tokio::spawn(run(room_id, self.features(room_id)));
+        rooms.insert(room_id, handle);
+        info!("[{room_id}] watching");
+        true
+    }
+
+    /// Stops watching a room. Returns `false` if the room is not watched.
+    pub async fn unwatch(&self, room_id: u32) -> bool {
+        match self.rooms.lock().await.remove(&room_id) {
+            Some(handle) => {
+                handle.abort();
+                info!("[{room_id}] unwatched");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Greets a room, trying each of its accounts until one succeeds.
+    pub async fn greet(&self, room_id: u32) -> Result<()> {
+        let mut result = Err(Error::MissingData("account"));
+        for account in self.accounts.for_room(room_id) {
+            result = send_greeting(&account.client, room_id).await;
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Checks the accounts at startup and periodically, alerting when one
+    /// needs to log in again.
+    pub async fn check_accounts(&self) {
+        let config = &self.config.credential;
+        loop {
+            for account in self.accounts.iter() {
+                match account.check(config).await {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        let message = format!("account {} needs to log in again", account.name);
+                        self.notifiers.notify(&Event::alert(message)).await;
+                    }
+                    Err(e) => error!("failed to check account {}: {e}", account.name),
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(config.interval)).await;
+        }
+    }
+
+    pub async fn rooms(&self) -> Vec<u32> {
+        let mut rooms: Vec<u32> = self.rooms.lock().await.keys().copied().collect();
+        rooms.sort();
+        rooms
+    }
+
+    fn features(&self, room_id: u32) -> Features {
+        // A guest without accounts only watches.
+        let accounts = self.accounts.for_room(room_id);
+        let sends = !accounts.is_empty();
+
+        let relay = match (
+            &self.onebot,
+            self.config.relays.iter().find(|r| r.room_id == room_id),
+        ) {
+            (Some(onebot), Some(config)) => Some(DanmuRelay::new(config.clone(), onebot.clone())),
+            _ => None,
+        };
+
+        let welcome = self
+            .config
+            .welcomes
+            .iter()
+            .find(|w| w.room_id == room_id)
+            .filter(|_| sends)
+            .map(|config| ViewerGreeter::new(config.clone()));
+
+        let thanks = self
+            .config
+            .thanks
+            .iter()
+            .find(|t| t.room_id == room_id)
+            .filter(|_| sends)
+            .map(|config| GiftThanksHandler::new(config.clone()));
+
+        let auto_reply = self
+            .config
+            .auto_replies
+            .iter()
+            .find(|a| a.room_id == room_id)
+            .filter(|_| sends)
+            .map(|config| AutoReplier::new(config.clone()));
+
+        let scheduler = self
+            .config
+            .schedules
+            .iter()
+            .find(|s| s.room_id == room_id)
+            .filter(|_| sends)
+            .map(|config| Scheduler::new(config.clone()));
+
+        let client = match &self.guest {
+            Some(guest) => guest.clone(),
+            None => accounts[0].client.clone(),
+        };
+
+        Features {
+            tracker: LiveTracker::new(),
+            client,
+            connect: self.config.connect.clone(),
+            accounts,
+            account: AtomicUsize::new(0),
+            greeting: sends.then(|| self.config.greeting.clone()),
+            pending: watch::Sender::new(None),
+            notifiers: self.notifiers.clone(),
+            limiter: self.limiter.clone(),
+            store: self.store.clone(),
+            relay,
+            welcome,
+            thanks,
+            auto_reply,
+            scheduler,
+        }
+    }
+}
+
+async fn run(room_id: u32, features: Features) {
+    loop {
+        let bot = RoomBot {
+            room_id,
+            greeting: features.greeting.as_ref().map(|config| {
+                LiveGreetingBot::new(
+                    room_id,
+                    config,
+                    &features.store,
+                    &features.pending,
+                    &features.tracker,
+                )
+            }),
+            sender: DanmuSender::new(
+                room_id,
+                &features.accounts,
+                &features.account,
+                &features.limiter,
+            ),
+            features: &features,
+        };
+
+        let result = tokio::select! {
+            r = connect_room(
+                &features.client,
+                room_id,
+                &features.connect,
+                features.tracker.track(&bot),
+            ) => r,
+            _ = bot.run() => Ok(()),
+        };
+
+        match result {
+            Ok(_) => (),
+            Err(e) => error!("failed to connect room {room_id}: {e}"),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
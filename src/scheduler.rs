@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use blive_greeting::LiveMessage;
+use tokio::sync::watch;
+use tracing::debug;
+
+use crate::config::ScheduleConfig;
+use crate::sender::DanmuSender;
+
+pub struct Scheduler {
+    config: ScheduleConfig,
+    live: watch::Sender<bool>,
+    next: AtomicUsize,
+}
+
+impl Scheduler {
+    pub fn new(config: ScheduleConfig) -> Self {
+        Self {
+            config,
+            live: watch::Sender::new(false),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn set_live(&self, live: bool) {
+        if self.live.send_replace(live) != live {
+            debug!("[{}] scheduler live: {live}", self.config.room_id);
+        }
+    }
+
+    pub fn handle_message(&self, message: &LiveMessage) {
+        match message {
+            LiveMessage::Live { .. } | LiveMessage::MissedLive { .. } => self.set_live(true),
+            LiveMessage::Preparing => self.set_live(false),
+            _ => (),
+        }
+    }
+
+    /// Posts the next message every `interval` seconds while the room is live.
+    pub async fn run(&self, sender: &DanmuSender<'_>) {
+        if self.config.messages.is_empty() {
+            return std::future::pending().await;
+        }
+
+        let interval = Duration::from_secs(self.config.interval);
+        let mut live = self.live.subscribe();
+        loop {
+            let _ = live.wait_for(|live| *live).await;
+
+            let offline = async {
+                let _ = live.wait_for(|live| !*live).await;
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {
+                    let i = self.next.fetch_add(1, Ordering::Relaxed) % self.config.messages.len();
+                    sender.send(&self.config.messages[i]).await;
+                }
+                _ = offline => (),
+            }
+        }
+    }
+}
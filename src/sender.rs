@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use blive_greeting::{Error, Result};
+use tracing::{debug, error, info, warn};
+
+use crate::accounts::Account;
+use crate::limiter::RateLimiter;
+
+/// Sends danmu to a room, falling back to the next account when the
+/// current one is banned or rate limited there.
+pub struct DanmuSender<'a> {
+    room_id: u32,
+    accounts: &'a [Arc<Account>],
+    current: &'a AtomicUsize,
+    limiter: &'a RateLimiter,
+}
+
+impl<'a> DanmuSender<'a> {
+    /// `current` is the index of the account in `accounts` in use.
+    pub fn new(
+        room_id: u32,
+        accounts: &'a [Arc<Account>],
+        current: &'a AtomicUsize,
+        limiter: &'a RateLimiter,
+    ) -> Self {
+        Self {
+            room_id,
+            accounts,
+            current,
+            limiter,
+        }
+    }
+
+    /// Whether the user is one of the accounts danmu are sent as.
+    pub fn is_own(&self, uid: u64) -> bool {
+        self.accounts
+            .iter()
+            .any(|a| a.client.credentials().uid() == Some(uid))
+    }
+
+    /// Returns whether the message is sent.
+    pub async fn send(&self, message: &str) -> bool {
+        self.reply(message, 0).await
+    }
+
+    /// Sends the message mentioning `reply_mid`. Returns whether it is sent.
+    pub async fn reply(&self, message: &str, reply_mid: u64) -> bool {
+        if !self.limiter.try_acquire().await {
+            debug!("[{}] rate limited: {message}", self.room_id);
+            return false;
+        }
+
+        match self.post(message, reply_mid).await {
+            Ok(_) => {
+                info!("[{}] sent: {message}", self.room_id);
+                true
+            }
+            Err(e) => {
+                error!("[{}] send danmu error: {e}", self.room_id);
+                false
+            }
+        }
+    }
+
+    /// Sends the message bypassing the rate limit, trying each account in
+    /// turn if rejected by Bilibili.
+    pub async fn post(&self, message: &str, reply_mid: u64) -> Result<()> {
+        let start = self.current.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for i in 0..self.accounts.len() {
+            let index = (start + i) % self.accounts.len();
+            let account = &self.accounts[index];
+            let name = &account.name;
+
+            match account
+                .client
+                .send_danmu(self.room_id, message, reply_mid)
+                .await
+            {
+                Ok(_) => {
+                    if index != start {
+                        info!("[{}] switched to account {name}", self.room_id);
+                        self.current.store(index, Ordering::Relaxed);
+                    }
+                    return Ok(());
+                }
+                Err(e @ Error::BiliResponse(..)) => {
+                    warn!("[{}] account {name} rejected: {e}", self.room_id);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or(Error::MissingData("account")))
+    }
+}
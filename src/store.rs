@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use blive_greeting::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoomState {
+    /// Unix time of the last greeting.
+    pub last_greeting: u64,
+    /// `live_time` of the last greeted broadcast session.
+    pub live_time: Option<i64>,
+}
+
+/// Per-room state persisted to a JSON file.
+pub struct StateStore {
+    path: PathBuf,
+    rooms: Mutex<HashMap<u32, RoomState>>,
+}
+
+impl StateStore {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let rooms = match tokio::fs::read_to_string(&path).await {
+            Ok(s) => serde_json::from_str(&s)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            rooms: Mutex::new(rooms),
+        })
+    }
+
+    pub async fn get(&self, room_id: u32) -> RoomState {
+        self.rooms
+            .lock()
+            .await
+            .get(&room_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub async fn set(&self, room_id: u32, state: RoomState) -> Result<()> {
+        let mut rooms = self.rooms.lock().await;
+        rooms.insert(room_id, state);
+
+        // Write to a temporary file first so that the store is never truncated.
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(&*rooms)?).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+
+        Ok(())
+    }
+}
@@ -1,132 +1,685 @@
-use brotli::Decompressor;
+use std::io::{Read, Write};
+
+use brotli::{CompressorWriter, Decompressor};
+use bytes::buf::Reader;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use flate2::read::GzDecoder;
+use flate2::bufread::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::Deserialize;
 use serde_json::json;
 use tokio_tungstenite::tungstenite::Message;
+use tokio_util::codec::{Decoder, Encoder};
 
 use crate::Error;
 
+/// Operation of a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Heartbeat,
+    HeartbeatReply,
+    Message,
+    Auth,
+    AuthReply,
+    Unknown(u32),
+}
+
+impl From<u32> for Operation {
+    fn from(value: u32) -> Self {
+        match value {
+            2 => Operation::Heartbeat,
+            3 => Operation::HeartbeatReply,
+            5 => Operation::Message,
+            7 => Operation::Auth,
+            8 => Operation::AuthReply,
+            op => Operation::Unknown(op),
+        }
+    }
+}
+
+impl From<Operation> for u32 {
+    fn from(value: Operation) -> Self {
+        match value {
+            Operation::Heartbeat => 2,
+            Operation::HeartbeatReply => 3,
+            Operation::Message => 5,
+            Operation::Auth => 7,
+            Operation::AuthReply => 8,
+            Operation::Unknown(op) => op,
+        }
+    }
+}
+
+/// Encoding of a packet body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtoVer {
+    /// Plain JSON.
+    Json,
+    /// Heartbeat and auth packets.
+    Int,
+    /// Zlib compressed packets.
+    Zlib,
+    /// Brotli compressed packets.
+    Brotli,
+    Unknown(u16),
+}
+
+impl From<u16> for ProtoVer {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => ProtoVer::Json,
+            1 => ProtoVer::Int,
+            2 => ProtoVer::Zlib,
+            3 => ProtoVer::Brotli,
+            ver => ProtoVer::Unknown(ver),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProtoVer {
+    /// Accepts only the known versions, as configured ones are sent to the
+    /// server.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match ProtoVer::from(u16::deserialize(deserializer)?) {
+            ProtoVer::Unknown(ver) => Err(serde::de::Error::custom(format!(
+                "unknown proto_ver {ver}, expected 0 to 3"
+            ))),
+            proto_ver => Ok(proto_ver),
+        }
+    }
+}
+
+impl From<ProtoVer> for u16 {
+    fn from(value: ProtoVer) -> Self {
+        match value {
+            ProtoVer::Json => 0,
+            ProtoVer::Int => 1,
+            ProtoVer::Zlib => 2,
+            ProtoVer::Brotli => 3,
+            ProtoVer::Unknown(ver) => ver,
+        }
+    }
+}
+
+/// The big-endian header preceding every packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    /// Length of the packet, including the header.
+    pub size: u32,
+    pub header_len: u16,
+    pub proto_ver: ProtoVer,
+    pub operation: Operation,
+    pub sequence: u32,
+}
+
+impl PacketHeader {
+    pub const LEN: usize = 16;
+
+    pub fn new(operation: Operation, proto_ver: ProtoVer, body_len: usize) -> Self {
+        Self {
+            size: (Self::LEN + body_len) as u32,
+            header_len: Self::LEN as u16,
+            proto_ver,
+            operation,
+            sequence: 1,
+        }
+    }
+
+    /// Whether the body is more packets, compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.operation == Operation::Message
+            && matches!(self.proto_ver, ProtoVer::Zlib | ProtoVer::Brotli)
+    }
+
+    /// Length of the body following the header.
+    pub fn body_len(&self) -> usize {
+        self.size as usize - self.header_len as usize
+    }
+
+    pub fn encode(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.size);
+        buf.put_u16(self.header_len);
+        buf.put_u16(self.proto_ver.into());
+        buf.put_u32(self.operation.into());
+        buf.put_u32(self.sequence);
+    }
+
+    /// Decodes a header, skipping any extension beyond the 16 known bytes.
+    pub fn decode(buf: &mut impl Buf) -> Result<Self, Error> {
+        if buf.remaining() < Self::LEN {
+            return Err(Error::DecodeSub("header"));
+        }
+
+        let header = Self {
+            size: buf.get_u32(),
+            header_len: buf.get_u16(),
+            proto_ver: buf.get_u16().into(),
+            operation: buf.get_u32().into(),
+            sequence: buf.get_u32(),
+        };
+
+        let extra = (header.header_len as usize)
+            .checked_sub(Self::LEN)
+            .ok_or(Error::DecodeSub("header"))?;
+        if header.size < header.header_len as u32 || buf.remaining() < extra {
+            return Err(Error::DecodeSub("header"));
+        }
+        buf.advance(extra);
+
+        Ok(header)
+    }
+}
+
+/// A packet to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub operation: Operation,
+    pub proto_ver: ProtoVer,
+    pub body: Bytes,
+}
+
+impl Packet {
+    pub fn new(operation: Operation, body: impl Into<Bytes>) -> Self {
+        Self {
+            operation,
+            proto_ver: ProtoVer::Int,
+            body: body.into(),
+        }
+    }
+
+    /// The packet as a websocket message.
+    pub fn to_message(&self) -> Message {
+        let mut buf = BytesMut::new();
+        self.encode(&mut buf);
+        Message::binary(buf.to_vec())
+    }
+
+    fn encode(&self, buf: &mut BytesMut) {
+        let header = PacketHeader::new(self.operation, self.proto_ver, self.body.len());
+        buf.reserve(header.size as usize);
+        header.encode(buf);
+        buf.put_slice(&self.body);
+    }
+}
+
 pub enum SubReply {
     Heartbeat(Bytes),
     Message(Bytes),
     Auth(Bytes),
+    /// A packet of an operation not handled here.
+    Unknown(PacketHeader, Bytes),
 }
 
-pub fn auth_sub(uid: u64, room_id: u32, buvid: &str, token: &str) -> Result<Message, Error> {
-    let bytes = encode_json(
-        json!({
-            "uid": uid,
-            "roomid": room_id,
-            "protover": 3,
-            "buvid": buvid,
-            "platform": "web",
-            "type": 2,
-            "key": token,
-        }),
-        7,
-    )?;
+impl From<SubReply> for Packet {
+    /// The packet to relay a reply as, uncompressed.
+    fn from(reply: SubReply) -> Self {
+        match reply {
+            SubReply::Heartbeat(body) => Packet::new(Operation::HeartbeatReply, body),
+            SubReply::Message(body) => message_reply(body),
+            SubReply::Auth(body) => Packet::new(Operation::AuthReply, body),
+            SubReply::Unknown(header, body) => Packet {
+                operation: header.operation,
+                proto_ver: header.proto_ver,
+                body,
+            },
+        }
+    }
+}
+
+/// Authenticates, asking for messages encoded as `proto_ver`.
+pub fn auth_sub(
+    uid: u64,
+    room_id: u32,
+    buvid: &str,
+    token: &str,
+    proto_ver: ProtoVer,
+) -> Result<Packet, Error> {
+    let body = serde_json::to_vec(&json!({
+        "uid": uid,
+        "roomid": room_id,
+        "protover": u16::from(proto_ver),
+        "buvid": buvid,
+        "platform": "web",
+        "type": 2,
+        "key": token,
+    }))?;
+
+    Ok(Packet::new(Operation::Auth, body))
+}
 
-    Ok(Message::binary(bytes))
+pub fn heartbeat_sub() -> Packet {
+    Packet::new(Operation::Heartbeat, "[object Object]")
 }
 
-pub fn heartbeat_sub() -> Message {
-    let bytes = encode_str("[object Object]", 2);
-    Message::binary(bytes)
+/// Replies to the auth of a client, `0` for success.
+pub fn auth_reply(code: i32) -> Packet {
+    Packet::new(Operation::AuthReply, format!(r#"{{"code":{code}}}"#))
 }
 
-fn encode_bytes(data: &[u8], op_code: u32) -> Vec<u8> {
-    let size = 16 + data.len();
-    let mut buf = BytesMut::with_capacity(size);
-    buf.put_u32(size as u32);
-    buf.put_u16(16);
-    buf.put_u16(1);
-    buf.put_u32(op_code);
-    buf.put_u32(1);
-    buf.put_slice(data);
-    buf.to_vec()
+/// Replies to a heartbeat. Bilibili no longer reports the popularity of a
+/// room here and always sends 1, which clients check for.
+pub fn heartbeat_reply() -> Packet {
+    Packet::new(Operation::HeartbeatReply, 1u32.to_be_bytes().to_vec())
 }
 
-fn encode_json<T>(data: T, op_code: u32) -> Result<Vec<u8>, Error>
-where
-    T: serde::Serialize,
-{
-    let json_string = serde_json::to_string(&data)?;
-    Ok(encode_str(&json_string, op_code))
+/// A plain JSON message such as `{"cmd":"DANMU_MSG",...}`.
+pub fn message_reply(body: impl Into<Bytes>) -> Packet {
+    Packet {
+        operation: Operation::Message,
+        proto_ver: ProtoVer::Json,
+        body: body.into(),
+    }
 }
 
-fn encode_str(data: &str, op_code: u32) -> Vec<u8> {
-    encode_bytes(data.as_bytes(), op_code)
+/// Compresses packets into one message packet, as the server batches them
+/// for clients asking for zlib or brotli.
+pub fn batch_reply(packets: &[Packet], proto_ver: ProtoVer) -> Result<Packet, Error> {
+    let mut buf = BytesMut::new();
+    for packet in packets {
+        packet.encode(&mut buf);
+    }
+
+    let body = match proto_ver {
+        ProtoVer::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&buf)?;
+            encoder.finish()?
+        }
+        ProtoVer::Brotli => {
+            let mut encoder = CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            encoder.write_all(&buf)?;
+            encoder.into_inner()
+        }
+        _ => return Err(Error::EncodeSub("batch without compression")),
+    };
+
+    Ok(Packet {
+        operation: Operation::Message,
+        proto_ver,
+        body: body.into(),
+    })
 }
 
+/// Largest packet accepted, compressed or inflated.
+pub const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+
+/// Deepest nesting of compressed packets accepted. Bilibili only compresses
+/// plain packets, so a compressed packet inside another is malformed.
+pub const MAX_DEPTH: usize = 1;
+
+/// Decodes every packet in a websocket message.
 pub fn decode(data: Vec<u8>) -> Result<Vec<SubReply>, Error> {
-    let mut replies = Vec::new();
-    decode_vec(data, &mut replies)?;
-    Ok(replies)
+    SubDecoder::new().decode(Bytes::from(data)).collect()
+}
+
+/// Decodes server packets, reusing the decompression state and buffers
+/// across messages. Keep one per connection.
+pub struct SubDecoder {
+    zlib: ZlibDecoder<Reader<Bytes>>,
+    /// Inflated bodies are split off this, so that its allocation is reused
+    /// once the replies of the previous message are dropped.
+    buffer: BytesMut,
+    /// The message, then the packets inflated from it not yet decoded.
+    stack: Vec<Bytes>,
+    /// Bytes the rest of the message may inflate to, so that many small
+    /// compressed packets cannot inflate without bound either.
+    budget: usize,
 }
 
-fn decode_vec(data: Vec<u8>, replies: &mut Vec<SubReply>) -> Result<(), Error> {
-    decode_bytes(Bytes::from(data), replies)
+impl Default for SubDecoder {
+    fn default() -> Self {
+        Self {
+            zlib: ZlibDecoder::new(Bytes::new().reader()),
+            buffer: BytesMut::new(),
+            stack: Vec::new(),
+            budget: 0,
+        }
+    }
 }
 
-fn decode_bytes(mut data: Bytes, replies: &mut Vec<SubReply>) -> Result<(), Error> {
-    if data.is_empty() {
-        return Ok(());
+impl SubDecoder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    if data.remaining() < 6 {
-        return Err(Error::DecodeSub("length"));
+    /// Returns the replies in `data` lazily. Bodies are slices of `data` or
+    /// of the inflated packets, so nothing is copied.
+    pub fn decode(&mut self, data: Bytes) -> Replies<'_> {
+        self.feed(data);
+        Replies { decoder: self }
     }
 
-    let size = data.get_u32() as usize;
-    let header_len = data.get_u16() as usize;
+    /// Starts decoding a message, whose replies are then returned by
+    /// `next_reply`. What is left of the previous message is dropped.
+    pub fn feed(&mut self, data: Bytes) {
+        self.stack.clear();
+        self.stack.push(data);
+        self.budget = MAX_PACKET_SIZE;
+    }
 
-    if header_len < 16 || data.remaining() < 10 {
-        return Err(Error::DecodeSub("header"));
+    /// The next reply of the message, or `None` once it is decoded. The rest
+    /// of the message is dropped after an error.
+    pub fn next_reply(&mut self) -> Result<Option<SubReply>, Error> {
+        let reply = self.decode_next();
+        if reply.is_err() {
+            self.stack.clear();
+        }
+        reply
     }
 
-    let kind = data.get_u16();
-    let op_code = data.get_u32();
-    let _sequence = data.get_u32();
+    fn decode_next(&mut self) -> Result<Option<SubReply>, Error> {
+        while let Some(data) = self.stack.last_mut() {
+            if !data.has_remaining() {
+                self.stack.pop();
+                continue;
+            }
+
+            let header = PacketHeader::decode(data)?;
+            if data.remaining() < header.body_len() {
+                return Err(Error::DecodeSub("body"));
+            }
+            let body = data.split_to(header.body_len());
+
+            // Checked before inflating, which is the expensive part.
+            if header.is_compressed() && self.stack.len() > MAX_DEPTH {
+                return Err(Error::DecodeSub("depth"));
+            }
 
-    if data.remaining() < size + 16 - 2 * header_len {
-        return Err(Error::DecodeSub("body"));
+            match self.decode_packet(header, body)? {
+                Decoded::Packets(packets) => {
+                    self.budget -= packets.len();
+                    self.stack.push(packets);
+                }
+                Decoded::Reply(reply) => return Ok(Some(reply)),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Decodes the body of a packet, returning the packets compressed in it
+    /// or the reply.
+    fn decode_packet(&mut self, header: PacketHeader, body: Bytes) -> Result<Decoded, Error> {
+        let decoded = match (header.proto_ver, header.operation) {
+            (ProtoVer::Zlib, Operation::Message) => {
+                self.zlib.reset(body.reader());
+                Decoded::Packets(inflate(&mut self.buffer, &mut self.zlib, self.budget)?)
+            }
+            (ProtoVer::Brotli, Operation::Message) => {
+                let decompressor = Decompressor::new(body.reader(), 4096);
+                Decoded::Packets(inflate(&mut self.buffer, decompressor, self.budget)?)
+            }
+            (_, Operation::Message) => Decoded::Reply(SubReply::Message(body)),
+            (_, Operation::HeartbeatReply) => Decoded::Reply(SubReply::Heartbeat(body)),
+            (_, Operation::AuthReply) => Decoded::Reply(SubReply::Auth(body)),
+            _ => Decoded::Reply(SubReply::Unknown(header, body)),
+        };
+
+        Ok(decoded)
+    }
+}
+
+enum Decoded {
+    Packets(Bytes),
+    Reply(SubReply),
+}
+
+/// Iterator over the replies of a message, see [`SubDecoder::decode`]. Ends
+/// after the first error.
+pub struct Replies<'a> {
+    decoder: &'a mut SubDecoder,
+}
+
+impl Iterator for Replies<'_> {
+    type Item = Result<SubReply, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder.next_reply().transpose()
+    }
+}
+
+/// Reads a decompressor to the end, up to `limit` bytes, into `buffer`.
+///
+/// `buffer` is kept zero-filled up to its capacity, so that it can be read
+/// into safely. Each region is filled once: what a message leaves is reused
+/// by the next one.
+fn inflate(buffer: &mut BytesMut, mut reader: impl Read, limit: usize) -> Result<Bytes, Error> {
+    let mut len = 0;
+    loop {
+        if len == buffer.len() {
+            buffer.reserve(len.max(256 * 1024));
+            buffer.resize(buffer.capacity(), 0);
+        }
+
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return Err(Error::DecodeSub("compressed body")),
+        }
+
+        if len > limit {
+            return Err(Error::DecodeSub("inflated size"));
+        }
+    }
+
+    Ok(buffer.split_to(len).freeze())
+}
+
+/// Frames packets on a byte stream such as the raw TCP danmu port.
+#[derive(Default)]
+pub struct SubCodec {
+    /// Decodes the packet last framed, which may be compressed.
+    decoder: SubDecoder,
+}
+
+impl SubCodec {
+    pub fn new() -> Self {
+        Self::default()
     }
+}
+
+impl Decoder for SubCodec {
+    type Item = SubReply;
+    type Error = Error;
 
-    let body = data.slice(header_len - 16..size - header_len);
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<SubReply>, Error> {
+        loop {
+            if let Some(reply) = self.decoder.next_reply()? {
+                return Ok(Some(reply));
+            }
+
+            if src.len() < PacketHeader::LEN {
+                return Ok(None);
+            }
 
-    match (kind, op_code) {
-        (0, 5) => replies.push(SubReply::Message(body)),
-        (_, 3) => {
-            return {
-                replies.push(SubReply::Heartbeat(body));
-                Ok(())
+            let size = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+            if size < PacketHeader::LEN {
+                return Err(Error::DecodeSub("length"));
             }
+            if size > MAX_PACKET_SIZE {
+                return Err(Error::DecodeSub("packet size"));
+            }
+            if src.len() < size {
+                src.reserve(size - src.len());
+                return Ok(None);
+            }
+
+            self.decoder.feed(src.split_to(size).freeze());
         }
-        (_, 8) => {
-            return {
-                replies.push(SubReply::Auth(body));
-                Ok(())
+    }
+}
+
+impl Encoder<Packet> for SubCodec {
+    type Error = Error;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<(), Error> {
+        packet.encode(dst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(operation: Operation, proto_ver: ProtoVer, body: &[u8]) -> Vec<u8> {
+        let packet = Packet {
+            operation,
+            proto_ver,
+            body: Bytes::copy_from_slice(body),
+        };
+        let mut buf = BytesMut::new();
+        packet.encode(&mut buf);
+        buf.to_vec()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut encoder = CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        encoder.into_inner()
+    }
+
+    const DANMU: &[u8] = r#"{"cmd":"DANMU_MSG","info":[[0,1,25],"晚上好",[1,"观众"]]}"#.as_bytes();
+    const ONLINE: &[u8] = br#"{"cmd":"ONLINE_RANK_COUNT","data":{"count":42}}"#;
+
+    /// A message as the server sends it: a heartbeat reply and two danmu
+    /// packets, compressed together unless `proto_ver` is plain.
+    fn fixture(proto_ver: ProtoVer) -> Vec<u8> {
+        let mut plain = packet(Operation::Message, ProtoVer::Json, DANMU);
+        plain.extend(packet(Operation::Message, ProtoVer::Json, ONLINE));
+
+        let mut message = packet(Operation::HeartbeatReply, ProtoVer::Int, &[0, 0, 0, 1]);
+        match proto_ver {
+            ProtoVer::Zlib => message.extend(packet(Operation::Message, proto_ver, &zlib(&plain))),
+            ProtoVer::Brotli => {
+                message.extend(packet(Operation::Message, proto_ver, &brotli(&plain)))
+            }
+            _ => {
+                message.extend(packet(Operation::Message, proto_ver, DANMU));
+                message.extend(packet(Operation::Message, proto_ver, ONLINE));
             }
         }
-        (2, _) => {
-            let mut decoder = GzDecoder::new(body.reader());
-            let mut output = Vec::new();
-            std::io::copy(&mut decoder, &mut output)?;
-            return decode_vec(output, replies);
+        message
+    }
+
+    fn assert_fixture(replies: &[SubReply]) {
+        assert_eq!(replies.len(), 3);
+        assert!(matches!(&replies[0], SubReply::Heartbeat(b) if b[..] == [0, 0, 0, 1]));
+        assert!(matches!(&replies[1], SubReply::Message(b) if b[..] == *DANMU));
+        assert!(matches!(&replies[2], SubReply::Message(b) if b[..] == *ONLINE));
+    }
+
+    const PROTO_VERS: [ProtoVer; 4] = [
+        ProtoVer::Json,
+        ProtoVer::Int,
+        ProtoVer::Zlib,
+        ProtoVer::Brotli,
+    ];
+
+    #[test]
+    fn decodes_each_proto_ver() {
+        for proto_ver in PROTO_VERS {
+            assert_fixture(&decode(fixture(proto_ver)).unwrap());
         }
-        (3, _) => {
-            let mut decompressor = Decompressor::new(body.reader(), 4096);
-            let mut output = Vec::new();
-            std::io::copy(&mut decompressor, &mut output)?;
-            return decode_vec(output, replies);
+    }
+
+    #[test]
+    fn codec_decodes_chunked_stream() {
+        for proto_ver in PROTO_VERS {
+            // Two messages, fed a few bytes at a time.
+            let stream = fixture(proto_ver).repeat(2);
+            let mut codec = SubCodec::new();
+            let mut src = BytesMut::new();
+            let mut replies = Vec::new();
+            for chunk in stream.chunks(7) {
+                src.extend_from_slice(chunk);
+                while let Some(reply) = codec.decode(&mut src).unwrap() {
+                    replies.push(reply);
+                }
+            }
+
+            assert!(src.is_empty());
+            assert_fixture(&replies[..3]);
+            assert_fixture(&replies[3..]);
         }
-        _ => return Ok(()),
     }
 
-    data.advance(size - header_len);
-    if data.has_remaining() {
-        return decode_bytes(data, replies);
+    #[test]
+    fn rejects_nested_compression() {
+        // Never inflated, or this would fail as a bad body instead.
+        let inner = packet(Operation::Message, ProtoVer::Brotli, b"not brotli");
+        let outer = packet(Operation::Message, ProtoVer::Zlib, &zlib(&inner));
+
+        assert!(matches!(decode(outer), Err(Error::DecodeSub("depth"))));
+    }
+
+    #[test]
+    fn limits_inflated_size_of_message() {
+        // Each inflates to 1 MiB, within the limit of a packet, but not
+        // together.
+        let plain = packet(Operation::Message, ProtoVer::Json, &vec![b' '; 1024 * 1024]);
+        let compressed = packet(Operation::Message, ProtoVer::Zlib, &zlib(&plain));
+        let bomb = compressed.repeat(MAX_PACKET_SIZE / plain.len() + 1);
+
+        assert!(matches!(
+            decode(bomb),
+            Err(Error::DecodeSub("inflated size"))
+        ));
+        assert_eq!(decode(compressed.repeat(4)).unwrap().len(), 4);
     }
 
-    Ok(())
+    #[test]
+    fn yields_replies_before_error() {
+        let mut message = fixture(ProtoVer::Json);
+        message.extend(packet(Operation::Message, ProtoVer::Zlib, b"not zlib"));
+
+        let mut decoder = SubDecoder::new();
+        let mut replies = decoder.decode(Bytes::from(message));
+        let decoded: Vec<_> = replies.by_ref().take(3).map(Result::unwrap).collect();
+        assert_fixture(&decoded);
+        assert!(replies.next().unwrap().is_err());
+        assert!(replies.next().is_none());
+    }
+
+    #[test]
+    fn decodes_batch_reply() {
+        let packets = [
+            heartbeat_reply(),
+            message_reply(DANMU),
+            message_reply(ONLINE),
+        ];
+        for proto_ver in [ProtoVer::Zlib, ProtoVer::Brotli] {
+            let batch = batch_reply(&packets[1..], proto_ver).unwrap();
+            let mut message = BytesMut::new();
+            packets[0].encode(&mut message);
+            batch.encode(&mut message);
+
+            assert_fixture(&decode(message.to_vec()).unwrap());
+        }
+        assert!(batch_reply(&packets, ProtoVer::Json).is_err());
+    }
+
+    #[test]
+    fn relays_replies_as_packets() {
+        for proto_ver in PROTO_VERS {
+            let replies = decode(fixture(proto_ver)).unwrap();
+            let mut message = BytesMut::new();
+            for reply in replies {
+                Packet::from(reply).encode(&mut message);
+            }
+
+            // Inflated, so the same whatever the original compression.
+            assert_eq!(message, fixture(ProtoVer::Json));
+            assert_fixture(&decode(message.to_vec()).unwrap());
+        }
+    }
 }
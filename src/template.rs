@@ -0,0 +1,53 @@
+/// Replaces `{name}` placeholders in `template` with the matching values.
+///
+/// Each `}` closes the innermost `{` before it, so placeholders may appear
+/// inside JSON objects. Unknown placeholders are kept as is.
+pub fn render<V: AsRef<str>>(template: &str, vars: &[(&str, V)]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(end) = rest.find('}') {
+        match rest[..end].rfind('{') {
+            Some(start) => {
+                output.push_str(&rest[..start]);
+                let name = &rest[start + 1..end];
+                match vars.iter().find(|(k, _)| *k == name) {
+                    Some((_, value)) => output.push_str(value.as_ref()),
+                    None => output.push_str(&rest[start..=end]),
+                }
+            }
+            None => output.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain() {
+        let vars = [("uname", "观众"), ("num", "2")];
+        assert_eq!(render("感谢 {uname} x{num}", &vars), "感谢 观众 x2");
+    }
+
+    #[test]
+    fn json() {
+        let vars = [("message", "live"), ("room_id", "1")];
+        assert_eq!(
+            render(r#"{"text": "{message}", "room": {room_id}}"#, &vars),
+            r#"{"text": "live", "room": 1}"#
+        );
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        let vars = [("uname", "观众")];
+        assert_eq!(render("{who} {uname} {", &vars), "{who} 观众 {");
+        assert_eq!(render("} {}", &vars), "} {}");
+    }
+}
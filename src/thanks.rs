@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use blive_greeting::template::render;
+use blive_greeting::LiveMessage;
+use tokio::sync::Mutex;
+
+use crate::config::ThanksConfig;
+use crate::sender::DanmuSender;
+
+pub struct GiftThanksHandler {
+    config: ThanksConfig,
+    pending: Mutex<HashMap<(u64, String), PendingGift>>,
+}
+
+struct PendingGift {
+    uname: String,
+    num: u64,
+    value: u64,
+    updated: Instant,
+}
+
+impl GiftThanksHandler {
+    pub fn new(config: ThanksConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn handle_message(&self, message: &LiveMessage, sender: &DanmuSender<'_>) {
+        match message {
+            LiveMessage::Gift(gift) if self.config.gift.is_some() => {
+                let mut pending = self.pending.lock().await;
+                let entry = pending
+                    .entry((gift.uid, gift.gift_name.clone()))
+                    .or_insert_with(|| PendingGift {
+                        uname: gift.uname.clone(),
+                        num: 0,
+                        value: 0,
+                        updated: Instant::now(),
+                    });
+                entry.num += gift.num;
+                if gift.coin_type == "gold" {
+                    entry.value += gift.total_coin;
+                }
+                entry.updated = Instant::now();
+            }
+            LiveMessage::Combo(combo) => {
+                // Keep collecting while the combo goes on.
+                let key = (combo.uid, combo.gift_name.clone());
+                if let Some(entry) = self.pending.lock().await.get_mut(&key) {
+                    entry.updated = Instant::now();
+                }
+            }
+            LiveMessage::GuardBuy(guard) => {
+                if let Some(template) = &self.config.guard {
+                    let message = render(
+                        template,
+                        &[
+                            ("uname", guard.uname.clone()),
+                            ("uid", guard.uid.to_string()),
+                            ("guard", guard.gift_name.clone()),
+                            ("num", guard.num.to_string()),
+                        ],
+                    );
+                    sender.send(&message).await;
+                }
+            }
+            LiveMessage::SuperChat(sc) if sc.price >= self.config.min_super_chat => {
+                if let Some(template) = &self.config.super_chat {
+                    let message = render(
+                        template,
+                        &[
+                            ("uname", sc.uname.clone()),
+                            ("uid", sc.uid.to_string()),
+                            ("price", sc.price.to_string()),
+                            ("message", sc.message.clone()),
+                        ],
+                    );
+                    sender.send(&message).await;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Thanks the gifts which have not been added to for `window` seconds.
+    pub async fn run(&self, sender: &DanmuSender<'_>) {
+        let window = Duration::from_secs(self.config.window);
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let expired: Vec<_> = {
+                let mut pending = self.pending.lock().await;
+                let keys: Vec<_> = pending
+                    .iter()
+                    .filter(|(_, gift)| gift.updated.elapsed() >= window)
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                keys.into_iter()
+                    .filter_map(|key| pending.remove_entry(&key))
+                    .collect()
+            };
+
+            let template = match &self.config.gift {
+                Some(template) => template,
+                None => continue,
+            };
+
+            for ((uid, gift_name), gift) in expired {
+                if gift.value < self.config.min_gift_value {
+                    continue;
+                }
+
+                let message = render(
+                    template,
+                    &[
+                        ("uname", gift.uname),
+                        ("uid", uid.to_string()),
+                        ("gift", gift_name),
+                        ("num", gift.num.to_string()),
+                    ],
+                );
+                sender.send(&message).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use md5::{Digest, Md5};
+
+/// Order of the characters of `img_key + sub_key` in the mixin key.
+const MIXIN_KEY_ENC_TAB: [usize; 64] = [
+    46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
+    28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60, 51, 30, 4, 22, 25,
+    54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
+];
+
+/// The keys change daily, so refetch them well before.
+const KEYS_TTL: Duration = Duration::from_secs(3600);
+
+/// Signs query parameters with the WBI keys from the nav endpoint.
+#[derive(Debug, Default)]
+pub(crate) struct WbiSigner {
+    mixin_key: Mutex<Option<(String, Instant)>>,
+}
+
+impl WbiSigner {
+    /// The cached mixin key, if fetched recently.
+    pub(crate) fn mixin_key(&self) -> Option<String> {
+        match &*self.mixin_key.lock().unwrap() {
+            Some((key, fetched)) if fetched.elapsed() < KEYS_TTL => Some(key.clone()),
+            _ => None,
+        }
+    }
+
+    /// Caches the mixin key from the `wbi_img` urls of the nav endpoint.
+    pub(crate) fn set_keys(&self, img_url: &str, sub_url: &str) -> String {
+        let key = mixin_key(key_of(img_url), key_of(sub_url));
+        *self.mixin_key.lock().unwrap() = Some((key.clone(), Instant::now()));
+        key
+    }
+
+    /// Drops the cached key, e.g. when a signed request is rejected.
+    pub(crate) fn invalidate(&self) {
+        *self.mixin_key.lock().unwrap() = None;
+    }
+}
+
+/// The file name without extension, e.g. the key in
+/// `https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png`.
+fn key_of(url: &str) -> &str {
+    let name = url.rsplit('/').next().unwrap_or(url);
+    name.split('.').next().unwrap_or(name)
+}
+
+fn mixin_key(img_key: &str, sub_key: &str) -> String {
+    let raw: Vec<char> = format!("{img_key}{sub_key}").chars().collect();
+    MIXIN_KEY_ENC_TAB
+        .iter()
+        .filter_map(|&i| raw.get(i))
+        .take(32)
+        .collect()
+}
+
+/// Adds `wts`, the current unix time, and `w_rid` to the parameters, in the
+/// order they are signed.
+pub(crate) fn sign(params: &[(&str, &str)], mixin_key: &str, wts: u64) -> Vec<(String, String)> {
+    let mut params: Vec<(String, String)> = params
+        .iter()
+        .map(|(k, v)| {
+            // These characters are dropped from values before signing.
+            let v = v.chars().filter(|c| !"!'()*".contains(*c)).collect();
+            (k.to_string(), v)
+        })
+        .chain([("wts".to_string(), wts.to_string())])
+        .collect();
+    params.sort();
+
+    let query = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", encode(k), encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let w_rid = format!("{:x}", Md5::digest(format!("{query}{mixin_key}")));
+
+    params.push(("w_rid".to_string(), w_rid));
+    params
+}
+
+/// Percent-encodes like `encodeURIComponent`.
+fn encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMG_URL: &str = "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png";
+    const SUB_URL: &str = "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png";
+
+    #[test]
+    fn mixin_key_of_urls() {
+        assert_eq!(key_of(IMG_URL), "7cd084941338484aae1ad9425b84077c");
+        assert_eq!(
+            WbiSigner::default().set_keys(IMG_URL, SUB_URL),
+            "ea1db124af3c7062474693fa704f4ff8"
+        );
+    }
+
+    #[test]
+    fn signs_documented_vector() {
+        let params = [("foo", "114"), ("bar", "514"), ("zab", "1919810")];
+        let signed = sign(&params, "ea1db124af3c7062474693fa704f4ff8", 1702204169);
+
+        let query: Vec<_> = signed.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        assert_eq!(
+            query,
+            [
+                "bar=514",
+                "foo=114",
+                "wts=1702204169",
+                "zab=1919810",
+                "w_rid=8f6f2b5b3d485fe1886cec6a0be8c5d4",
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_reserved_characters() {
+        let signed = sign(&[("q", "a!'()*b")], "ea1db124af3c7062474693fa704f4ff8", 1);
+        assert_eq!(signed[0], ("q".to_string(), "ab".to_string()));
+    }
+}
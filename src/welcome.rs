@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use blive_greeting::template::render;
+use blive_greeting::{InteractKind, LiveMessage};
+use tokio::sync::Mutex;
+
+use crate::config::WelcomeConfig;
+use crate::sender::DanmuSender;
+
+pub struct ViewerGreeter {
+    config: WelcomeConfig,
+    last_greeted: Mutex<HashMap<u64, Instant>>,
+}
+
+impl ViewerGreeter {
+    pub fn new(config: WelcomeConfig) -> Self {
+        Self {
+            config,
+            last_greeted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn handle_message(&self, message: &LiveMessage, sender: &DanmuSender<'_>) {
+        let (uid, uname, template, guard) = match message {
+            LiveMessage::Interact(word) => {
+                let level = word.medal.as_ref().map_or(0, |m| m.level);
+                let template = match word.kind {
+                    InteractKind::Enter if level >= self.config.medal_level => &self.config.enter,
+                    InteractKind::Follow => &self.config.follow,
+                    _ => return,
+                };
+                (word.uid, &word.uname, template, "")
+            }
+            LiveMessage::EntryEffect(entry) => {
+                // Entry effects are also shown for non-guards, e.g. high
+                // level viewers.
+                let guard = match entry.guard_level {
+                    1 => "总督",
+                    2 => "提督",
+                    3 => "舰长",
+                    _ => return,
+                };
+                (entry.uid, &entry.uname, &self.config.guard, guard)
+            }
+            _ => return,
+        };
+
+        let template = match template {
+            Some(template) => template,
+            None => return,
+        };
+
+        if self.config.blocklist.contains(&uid) {
+            return;
+        }
+
+        let cooldown = Duration::from_secs(self.config.cooldown);
+        if let Some(t) = self.last_greeted.lock().await.get(&uid) {
+            if t.elapsed() < cooldown {
+                return;
+            }
+        }
+
+        let uid_str = uid.to_string();
+        let message = render(
+            template,
+            &[
+                ("uname", uname.as_str()),
+                ("uid", &uid_str),
+                ("guard", guard),
+            ],
+        );
+
+        if sender.send(&message).await {
+            let mut last_greeted = self.last_greeted.lock().await;
+            last_greeted.retain(|_, t| t.elapsed() < cooldown);
+            last_greeted.insert(uid, Instant::now());
+        }
+    }
+}